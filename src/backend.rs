@@ -0,0 +1,67 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use smithay_client_toolkit::{
+    output::OutputInfo, reexports::client::protocol::wl_output::WlOutput,
+};
+
+use crate::buffer::Color;
+
+/// A way of dimming, driven along the fade by [`DimData`](crate::DimData). Several may dim at
+/// once, e.g. the overlay along with the backlight, and more can be given with
+/// [`DimData::add_backend`](crate::DimData::add_backend).
+pub trait DimBackend {
+    /// Name to tell the backend by in logs
+    fn name(&self) -> &str;
+
+    fn capabilities(&self) -> Capabilities;
+
+    /// Get ready as a dim starts. A backend failing to do so is left out of that dim.
+    fn prepare(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Dim the given output as well, if [`Capabilities::per_output`]. May be called again for an
+    /// output already added, e.g. once its mode changes.
+    fn add_output(&mut self, _output: &Output) {}
+
+    /// Stop dimming the given output, if [`Capabilities::per_output`], as it is gone.
+    fn remove_output(&mut self, _output: &WlOutput) {}
+
+    /// Show the given level. Called repeatedly while it changes, so showing the same level again
+    /// should be cheap.
+    fn apply(&mut self, level: Level);
+
+    /// Undo everything done since [`DimBackend::prepare`], as the dim has ended.
+    fn restore(&mut self);
+}
+
+/// What a [`DimBackend`] is able to do
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Whether the color of [`Level`] is shown, rather than only darkening
+    pub color: bool,
+    /// Whether outputs are dimmed one by one, see [`DimBackend::add_output`]
+    pub per_output: bool,
+    /// Whether the backend receives input which ends the dim, so we need not ask the compositor
+    /// about activity
+    pub input: bool,
+}
+
+/// How dim a backend should be at some point of the dim
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Level {
+    /// How much to dim, 0.0 not at all and 1.0 fully
+    pub alpha: f32,
+    pub color: Color,
+    /// Time since the dim started
+    pub elapsed: Duration,
+}
+
+/// An output announced by the compositor
+#[derive(Debug, Clone)]
+pub struct Output {
+    pub wl_output: WlOutput,
+    /// What the compositor told us about it so far, if anything
+    pub info: Option<OutputInfo>,
+}
@@ -0,0 +1,449 @@
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use log::{debug, info, warn};
+#[cfg(feature = "dbus")]
+use zbus::blocking::Connection;
+
+use crate::{
+    backend::{Capabilities, DimBackend, Level},
+    runtime,
+};
+
+/// Records the brightness devices had before we dimmed them, so it can be restored should we die
+/// without doing so. Holds our PID and start time on the first line, then a line per device with
+/// its original brightness and directory.
+const STATE_FILENAME: &str = "backlight.state";
+
+/// Backlight devices dimmed along with the fade, restored to their original brightness once
+/// done or dropped
+pub struct Backlight {
+    /// Directory holding the devices, e.g. `/sys/class/backlight`
+    root: PathBuf,
+    devices: Vec<Device>,
+    /// Where to keep the state file, our runtime directory unless testing
+    runtime: Option<PathBuf>,
+    state: Option<PathBuf>,
+    logind: Logind,
+}
+
+#[derive(Debug)]
+struct Device {
+    /// Directory of the device, e.g. `/sys/class/backlight/intel_backlight`
+    dir: PathBuf,
+    original: u32,
+    current: u32,
+}
+
+impl Backlight {
+    /// Dim the backlight devices within the given directory, e.g. `/sys/class/backlight`, once
+    /// prepared.
+    pub fn new(root: PathBuf) -> Self {
+        Self::with_runtime(root, runtime::dir())
+    }
+
+    fn with_runtime(root: PathBuf, runtime: Option<PathBuf>) -> Self {
+        Self {
+            root,
+            devices: Vec::new(),
+            runtime,
+            state: None,
+            logind: Logind::new(),
+        }
+    }
+}
+
+impl DimBackend for Backlight {
+    fn name(&self) -> &str {
+        "backlight"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+
+    /// Take over every device, noting their current brightness to restore later.
+    fn prepare(&mut self) -> Result<()> {
+        recover(&mut self.logind, self.runtime.as_deref())?;
+
+        let root = &self.root;
+        let mut devices = Vec::new();
+        for entry in fs::read_dir(root).with_context(|| format!("Failed to read {root:?}"))? {
+            let dir = entry?.path();
+            match read_value(&dir.join("brightness")) {
+                Ok(original) => devices.push(Device {
+                    dir,
+                    original,
+                    current: original,
+                }),
+                Err(e) => debug!("Skipping {dir:?}: {e}"),
+            }
+        }
+
+        if devices.is_empty() {
+            bail!("No backlight devices in {root:?}");
+        }
+
+        self.state = self
+            .runtime
+            .as_deref()
+            .context("XDG_RUNTIME_DIR is not set")
+            .and_then(|dir| save_state(dir, &devices))
+            .inspect_err(|e| warn!("Backlight will not be restored should dim crash: {e}"))
+            .ok();
+        self.devices = devices;
+        Ok(())
+    }
+
+    /// Dim like an overlay of the given alpha would, 0.0 keeping the original brightness and 1.0
+    /// turning the backlight off.
+    fn apply(&mut self, level: Level) {
+        for device in &mut self.devices {
+            let brightness =
+                (device.original as f32 * (1. - level.alpha.clamp(0., 1.))).round() as u32;
+            if brightness == device.current {
+                continue;
+            }
+
+            match write_brightness(&mut self.logind, &device.dir, brightness) {
+                Ok(()) => device.current = brightness,
+                Err(e) => warn!("Failed to set brightness of {:?}: {e:#}", device.dir),
+            }
+        }
+    }
+
+    fn restore(&mut self) {
+        for device in self.devices.drain(..) {
+            if let Err(e) = write_brightness(&mut self.logind, &device.dir, device.original) {
+                warn!("Failed to restore brightness of {:?}: {e:#}", device.dir);
+            }
+        }
+
+        if let Some(state) = self.state.take() {
+            if let Err(e) = fs::remove_file(&state) {
+                debug!("Failed to remove {state:?}: {e}");
+            }
+        }
+    }
+}
+
+impl Drop for Backlight {
+    fn drop(&mut self) {
+        self.restore();
+    }
+}
+
+fn read_value(path: &Path) -> Result<u32> {
+    Ok(fs::read_to_string(path)?.trim().parse()?)
+}
+
+/// Set the brightness of the device in the given directory, through logind if we may not.
+fn write_brightness(logind: &mut Logind, dir: &Path, brightness: u32) -> Result<()> {
+    match fs::write(dir.join("brightness"), brightness.to_string()) {
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+            let name = dir
+                .file_name()
+                .and_then(|name| name.to_str())
+                .ok_or_else(|| anyhow!("No device name in {dir:?}"))?;
+            logind.set_brightness(name, brightness)
+        }
+        result => Ok(result?),
+    }
+}
+
+/// Note the brightness of the given devices in the given runtime directory.
+fn save_state(dir: &Path, devices: &[Device]) -> Result<PathBuf> {
+    let pid = process::id();
+    let start_time = runtime::start_time(pid).context("No start time")?;
+
+    runtime::create(dir)?;
+    let path = dir.join(STATE_FILENAME);
+    let mut file = File::create(&path)?;
+    writeln!(file, "{pid} {start_time}")?;
+    for device in devices {
+        writeln!(file, "{} {}", device.original, device.dir.display())?;
+    }
+
+    Ok(path)
+}
+
+/// Restore the brightness noted in the given runtime directory by an instance which died while
+/// dimming the backlight, or by ourselves. Fails if another live instance is dimming it, which
+/// will restore it itself.
+fn recover(logind: &mut Logind, dir: Option<&Path>) -> Result<()> {
+    let Some(path) = dir.map(|dir| dir.join(STATE_FILENAME)) else {
+        return Ok(());
+    };
+    let Ok(state) = fs::read_to_string(&path) else {
+        return Ok(());
+    };
+
+    let mut lines = state.lines();
+    let owner = lines.next().and_then(|line| line.split_once(' '));
+    if let Some((pid, started)) = owner {
+        // left by an earlier dim of our own, e.g. of the daemon
+        let ours = pid.parse() == Ok(process::id());
+        let alive = pid.parse().ok().and_then(runtime::start_time);
+        if !ours && alive.is_some_and(|time| time.to_string() == started) {
+            bail!("Backlight is already being dimmed by dim {pid}");
+        }
+    }
+
+    for line in lines {
+        let Some((brightness, dir)) = line.split_once(' ') else {
+            continue;
+        };
+        let Ok(brightness) = brightness.parse() else {
+            continue;
+        };
+
+        info!("Restoring brightness of {dir} left by a previous dim");
+        if let Err(e) = write_brightness(logind, Path::new(dir), brightness) {
+            warn!("Failed to restore brightness of {dir}: {e:#}");
+        }
+    }
+
+    if let Err(e) = fs::remove_file(&path) {
+        debug!("Failed to remove {path:?}: {e}");
+    }
+
+    Ok(())
+}
+
+/// Restore the brightness left by an instance which died while dimming the backlight, if any.
+pub fn recover_leftovers() {
+    if let Err(e) = recover(&mut Logind::new(), runtime::dir().as_deref()) {
+        debug!("{e}");
+    }
+}
+
+/// The session on the system bus, which may set the brightness for us when we may not
+#[cfg(feature = "dbus")]
+struct Logind(Option<Connection>);
+
+#[cfg(feature = "dbus")]
+impl Logind {
+    /// Connect lazily, as we may never need to
+    fn new() -> Self {
+        Self(None)
+    }
+
+    fn set_brightness(&mut self, name: &str, brightness: u32) -> Result<()> {
+        let conn = match &self.0 {
+            Some(conn) => conn.clone(),
+            None => self.0.insert(Connection::system()?).clone(),
+        };
+
+        conn.call_method(
+            Some("org.freedesktop.login1"),
+            "/org/freedesktop/login1/session/auto",
+            Some("org.freedesktop.login1.Session"),
+            "SetBrightness",
+            &("backlight", name, brightness),
+        )?;
+
+        Ok(())
+    }
+}
+
+/// Stand-in for when we are built without D-Bus support, so brightness may only be written
+#[cfg(not(feature = "dbus"))]
+struct Logind;
+
+#[cfg(not(feature = "dbus"))]
+impl Logind {
+    fn new() -> Self {
+        Self
+    }
+
+    fn set_brightness(&mut self, _: &str, _: u32) -> Result<()> {
+        Err(anyhow!(
+            "Not allowed to write brightness, and dim was built without the dbus feature to ask logind"
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{mem, os::unix::process::parent_id, time::Duration};
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    /// A fake sysfs with a device of the given brightness, and runtime directory for the state
+    struct Sysfs {
+        root: TempDir,
+        runtime: TempDir,
+    }
+
+    impl Sysfs {
+        fn new(brightness: u32) -> Self {
+            let root = TempDir::new().unwrap();
+            let runtime = TempDir::new().unwrap();
+
+            let device = root.path().join("panel");
+            fs::create_dir(&device).unwrap();
+            fs::write(device.join("brightness"), format!("{brightness}\n")).unwrap();
+            fs::write(device.join("max_brightness"), "1000\n").unwrap();
+            // not a device, so skipped
+            fs::create_dir(root.path().join("empty")).unwrap();
+
+            Self { root, runtime }
+        }
+
+        fn backlight(&self) -> Backlight {
+            Backlight::with_runtime(self.root.path().to_owned(), Some(self.dir()))
+        }
+
+        /// Our directory within the runtime directory
+        fn dir(&self) -> PathBuf {
+            self.runtime.path().join("dim")
+        }
+
+        fn brightness(&self) -> u32 {
+            read_value(&self.root.path().join("panel/brightness")).unwrap()
+        }
+
+        fn state(&self) -> PathBuf {
+            self.dir().join(STATE_FILENAME)
+        }
+    }
+
+    fn level(alpha: f32) -> Level {
+        Level {
+            alpha,
+            color: "#000000".parse().unwrap(),
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn dims_and_restores() {
+        let sysfs = Sysfs::new(800);
+        let mut backlight = sysfs.backlight();
+        backlight.prepare().unwrap();
+
+        backlight.apply(level(0.25));
+        assert_eq!(sysfs.brightness(), 600);
+        backlight.apply(level(1.));
+        assert_eq!(sysfs.brightness(), 0);
+
+        backlight.restore();
+        assert_eq!(sysfs.brightness(), 800);
+    }
+
+    #[test]
+    fn restores_once_dropped() {
+        let sysfs = Sysfs::new(800);
+        let mut backlight = sysfs.backlight();
+        backlight.prepare().unwrap();
+        backlight.apply(level(0.5));
+
+        drop(backlight);
+        assert_eq!(sysfs.brightness(), 800);
+    }
+
+    #[test]
+    fn state_kept_while_dimming() {
+        let sysfs = Sysfs::new(800);
+        let mut backlight = sysfs.backlight();
+        backlight.prepare().unwrap();
+
+        let state = fs::read_to_string(sysfs.state()).unwrap();
+        let mut lines = state.lines();
+        let pid = process::id();
+        assert_eq!(
+            lines.next(),
+            Some(format!("{pid} {}", runtime::start_time(pid).unwrap()).as_str())
+        );
+        let device = sysfs.root.path().join("panel");
+        assert_eq!(
+            lines.next(),
+            Some(format!("800 {}", device.display()).as_str())
+        );
+        assert_eq!(lines.next(), None);
+
+        backlight.restore();
+        assert!(!sysfs.state().exists());
+    }
+
+    #[test]
+    fn refuses_backlight_dimmed_by_live_instance() {
+        let sysfs = Sysfs::new(400);
+        // a live instance other than us, as played by our parent
+        let pid = parent_id();
+        let started = runtime::start_time(pid).unwrap();
+        fs::create_dir(sysfs.dir()).unwrap();
+        let device = sysfs.root.path().join("panel");
+        fs::write(
+            sysfs.state(),
+            format!("{pid} {started}\n800 {}\n", device.display()),
+        )
+        .unwrap();
+
+        assert!(sysfs.backlight().prepare().is_err());
+        assert_eq!(sysfs.brightness(), 400);
+        assert!(sysfs.state().exists());
+    }
+
+    #[test]
+    fn recovers_state_of_our_own() {
+        let sysfs = Sysfs::new(800);
+        let mut backlight = sysfs.backlight();
+        backlight.prepare().unwrap();
+        backlight.apply(level(0.5));
+        // an earlier dim of this process left its state behind
+        mem::forget(backlight);
+        assert_eq!(sysfs.brightness(), 400);
+
+        let mut backlight = sysfs.backlight();
+        backlight.prepare().unwrap();
+        assert_eq!(sysfs.brightness(), 800);
+        backlight.restore();
+        assert!(!sysfs.state().exists());
+    }
+
+    #[test]
+    fn recovers_after_crash() {
+        let sysfs = Sysfs::new(800);
+        let mut backlight = sysfs.backlight();
+        backlight.prepare().unwrap();
+        backlight.apply(level(0.5));
+        // crash without restoring, leaving the state of a dead instance
+        mem::forget(backlight);
+        let state = fs::read_to_string(sysfs.state()).unwrap();
+        let (_, devices) = state.split_once('\n').unwrap();
+        fs::write(sysfs.state(), format!("{} 0\n{devices}", u32::MAX)).unwrap();
+        assert_eq!(sysfs.brightness(), 400);
+
+        recover(&mut Logind::new(), Some(&sysfs.dir())).unwrap();
+        assert_eq!(sysfs.brightness(), 800);
+        assert!(!sysfs.state().exists());
+    }
+
+    #[test]
+    fn recovers_before_dimming() {
+        let sysfs = Sysfs::new(400);
+        fs::create_dir(sysfs.dir()).unwrap();
+        let device = sysfs.root.path().join("panel");
+        fs::write(
+            sysfs.state(),
+            format!("{} 0\n800 {}\n", u32::MAX, device.display()),
+        )
+        .unwrap();
+
+        let mut backlight = sysfs.backlight();
+        backlight.prepare().unwrap();
+        assert_eq!(sysfs.brightness(), 800);
+
+        backlight.apply(level(0.5));
+        backlight.restore();
+        assert_eq!(sysfs.brightness(), 800);
+    }
+}
@@ -1,3 +1,7 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::anyhow;
+use serde::Deserialize;
 use smithay_client_toolkit::{
     reexports::{
         client::{
@@ -15,6 +19,48 @@ use smithay_client_toolkit::{
 
 use crate::DimData;
 
+/// Color of the overlay, given as `#RRGGBB`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl FromStr for Color {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s
+            .strip_prefix('#')
+            // from_str_radix would take a sign, and slicing needs single byte characters
+            .filter(|hex| hex.len() == 6 && hex.bytes().all(|b| b.is_ascii_hexdigit()))
+            .ok_or_else(|| anyhow!("Colors must be given as #RRGGBB."))?;
+
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16);
+        Ok(Self {
+            r: channel(0)?,
+            g: channel(2)?,
+            b: channel(4)?,
+        })
+    }
+}
+
+impl TryFrom<String> for Color {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+}
+
 /// Abstracts away which is the best buffer manager available
 pub enum BufferManager {
     SinglePixel(SimpleGlobal<WpSinglePixelBufferManagerV1, 1>),
@@ -29,17 +75,29 @@ pub enum BufferType {
 
 impl BufferManager {
     /// Generate a new buffer from the owned buffer manager type
-    pub fn get_buffer(&mut self, qh: &QueueHandle<DimData>, alpha: f32) -> BufferType {
+    pub fn get_buffer(
+        &mut self,
+        qh: &QueueHandle<DimData>,
+        color: Color,
+        alpha: f32,
+    ) -> BufferType {
         match self {
             BufferManager::SinglePixel(simple_global) => {
                 // pre-multiply alpha
-                let alpha = (u32::MAX as f32 * alpha) as u32;
+                let channel = |c: u8| (u32::MAX as f32 * (c as f32 / 255.) * alpha) as u32;
 
                 BufferType::Wl(
                     simple_global
                         .get()
                         .expect("failed to get buffer")
-                        .create_u32_rgba_buffer(0, 0, 0, alpha, qh, ()),
+                        .create_u32_rgba_buffer(
+                            channel(color.r),
+                            channel(color.g),
+                            channel(color.b),
+                            (u32::MAX as f32 * alpha) as u32,
+                            qh,
+                            (),
+                        ),
                 )
             }
 
@@ -49,18 +107,80 @@ impl BufferManager {
                     .create_buffer(1, 1, 4, wl_shm::Format::Argb8888)
                     .expect("Failed to get buffer from slot pool!");
 
-                BufferManager::paint(canvas, alpha);
+                BufferManager::paint(canvas, color, alpha);
                 BufferType::Shared(buffer)
             }
         }
     }
 
-    pub fn paint(canvas: &mut [u8], alpha: f32) {
-        // RGB
-        (0..3).for_each(|i| {
-            canvas[i] = 0;
-        });
+    /// Make the given buffer show the given color and alpha, replacing it if needed
+    pub fn refill(
+        &mut self,
+        qh: &QueueHandle<DimData>,
+        buffer: &mut BufferType,
+        color: Color,
+        alpha: f32,
+    ) {
+        match self {
+            BufferManager::SinglePixel(..) => *buffer = self.get_buffer(qh, color, alpha),
+            BufferManager::Shm(_, pool) => {
+                if let BufferType::Shared(buffer) = buffer {
+                    let canvas = buffer.canvas(pool).expect("Canvas is not drawable.");
+                    BufferManager::paint(canvas, color, alpha);
+                }
+            }
+        }
+    }
+
+    pub fn paint(canvas: &mut [u8], color: Color, alpha: f32) {
+        // BGR, pre-multiplied as with single pixel buffers
+        for (i, c) in [color.b, color.g, color.r].into_iter().enumerate() {
+            canvas[i] = (c as f32 * alpha) as u8;
+        }
         // ...A
         canvas[3] = (u8::MAX as f32 * alpha) as u8;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Color;
+
+    #[test]
+    fn parses_hex() {
+        assert_eq!("#000000".parse::<Color>().unwrap(), Color::default());
+        assert_eq!(
+            "#1a2B3c".parse::<Color>().unwrap(),
+            Color {
+                r: 0x1a,
+                g: 0x2b,
+                b: 0x3c
+            }
+        );
+        assert_eq!(
+            "#FFFFFF".parse::<Color>().unwrap(),
+            Color {
+                r: 255,
+                g: 255,
+                b: 255
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_other_forms() {
+        for color in [
+            "", "#", "000000", "#fff", "#0000000", "#00000", "#gg0000", "# 00000", "#+10000",
+            "black", "#00000é", "#0é000", "#0000é",
+        ] {
+            assert!(color.parse::<Color>().is_err(), "{color:?} was accepted");
+        }
+    }
+
+    #[test]
+    fn displays_as_parsed() {
+        for color in ["#000000", "#1a2b3c", "#ffffff"] {
+            assert_eq!(color.parse::<Color>().unwrap().to_string(), color);
+        }
+    }
+}
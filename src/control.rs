@@ -0,0 +1,221 @@
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::PathBuf,
+    str::FromStr,
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use log::{debug, warn};
+use smithay_client_toolkit::reexports::calloop::{
+    channel::{self, Event},
+    LoopHandle,
+};
+
+use crate::{buffer::Color, runtime};
+
+/// How long a client waits for us to answer
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Requests which may be sent to a running dim, one per line, each answered by a single line
+/// which starts with `error: ` if the request failed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Request {
+    /// Answer with `key=value` pairs describing what we are doing
+    Status,
+    /// End the dim as if input was detected
+    Cancel,
+    /// End the dim as if its duration passed
+    Finish,
+    /// Add the given seconds to the duration of the current dim
+    Extend(u64),
+    SetAlpha(f32),
+    SetColor(Color),
+}
+
+impl FromStr for Request {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let request = match (words.next(), words.next()) {
+            (Some("status"), None) => Request::Status,
+            (Some("cancel"), None) => Request::Cancel,
+            (Some("finish"), None) => Request::Finish,
+            (Some("extend"), Some(secs)) => Request::Extend(secs.parse()?),
+            (Some("set"), Some(setting)) => match setting.split_once('=') {
+                Some(("alpha", alpha)) => {
+                    let alpha = alpha.parse()?;
+                    if !(0.0..=1.0).contains(&alpha) {
+                        bail!("Alpha can only be from 0.0 to 1.0 inclusive.");
+                    }
+                    Request::SetAlpha(alpha)
+                }
+                Some(("color", color)) => Request::SetColor(color.parse()?),
+                _ => bail!("Unknown setting `{setting}`, expected alpha=<ALPHA> or color=<COLOR>"),
+            },
+            _ => bail!("Unknown request `{s}`"),
+        };
+
+        match words.next() {
+            Some(extra) => Err(anyhow!("Unexpected `{extra}` in request")),
+            None => Ok(request),
+        }
+    }
+}
+
+/// What requests, and the signals standing for them, act on: a dim of either display
+pub trait Controlled {
+    /// Act on the given request, answering it
+    fn handle_request(&mut self, request: Request) -> Result<String>;
+
+    /// Stop because of SIGTERM or SIGINT, see [`signals::listen`](crate::signals::listen)
+    fn interrupt(&mut self);
+}
+
+/// Where the dim on the current display listens for requests
+pub fn socket_path() -> Option<PathBuf> {
+    runtime::dir().map(|dir| dir.join(format!("ctl-{}.sock", runtime::display())))
+}
+
+/// Listen for requests on our socket, handling them within the event loop. Should only be
+/// called while we are the only dim on this display, as any existing socket is replaced.
+pub fn listen<D: Controlled + 'static>(handle: &LoopHandle<'static, D>) -> Result<()> {
+    runtime::create_dir()?;
+    let path = socket_path().context("XDG_RUNTIME_DIR is not set")?;
+    if path.exists() {
+        fs::remove_file(&path)?;
+    }
+    let listener = UnixListener::bind(&path).with_context(|| format!("Failed to bind {path:?}"))?;
+
+    let (sender, receiver) = channel::channel::<(Request, mpsc::Sender<String>)>();
+    handle
+        .insert_source(receiver, move |event, _, data: &mut D| {
+            if let Event::Msg((request, reply)) = event {
+                let answer = data.handle_request(request);
+                let _ = reply.send(answer.unwrap_or_else(|e| format!("error: {e}")));
+            }
+        })
+        .map_err(|e| e.error)?;
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                // each client is served on its own, so one which never sends a request does not
+                // hold up the others
+                Ok(stream) => {
+                    let sender = sender.clone();
+                    thread::spawn(move || {
+                        if let Err(e) = serve(stream, &sender) {
+                            debug!("Control client failed: {e}");
+                        }
+                    });
+                }
+                Err(e) => warn!("Failed to accept control client: {e}"),
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Answer each request of a client until it hangs up.
+fn serve(
+    stream: UnixStream,
+    sender: &channel::Sender<(Request, mpsc::Sender<String>)>,
+) -> Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let answer = match line?.parse::<Request>() {
+            Ok(request) => {
+                let (reply, answer) = mpsc::channel();
+                sender.send((request, reply))?;
+                answer.recv()?
+            }
+            Err(e) => format!("error: {e}"),
+        };
+
+        writeln!(writer, "{answer}")?;
+    }
+
+    Ok(())
+}
+
+/// Send a request to the dim on the current display, returning its answer.
+pub fn send(request: &str) -> Result<String> {
+    let path = socket_path().context("XDG_RUNTIME_DIR is not set")?;
+    let mut stream =
+        UnixStream::connect(&path).with_context(|| format!("No dim is listening at {path:?}"))?;
+    stream.set_read_timeout(Some(REPLY_TIMEOUT))?;
+
+    writeln!(stream, "{request}")?;
+    let mut answer = String::new();
+    BufReader::new(stream).read_line(&mut answer)?;
+
+    match answer.trim_end().strip_prefix("error: ") {
+        Some(e) => Err(anyhow!("{e}")),
+        None if answer.is_empty() => Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+        None => Ok(answer.trim_end().to_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Request;
+    use crate::buffer::Color;
+
+    fn parse(s: &str) -> Request {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn simple_requests() {
+        assert_eq!(parse("status"), Request::Status);
+        assert_eq!(parse("cancel"), Request::Cancel);
+        assert_eq!(parse("  finish\t"), Request::Finish);
+    }
+
+    #[test]
+    fn extend() {
+        assert_eq!(parse("extend 30"), Request::Extend(30));
+        assert_eq!(parse("extend   0"), Request::Extend(0));
+        assert!("extend".parse::<Request>().is_err());
+        assert!("extend -5".parse::<Request>().is_err());
+        assert!("extend soon".parse::<Request>().is_err());
+    }
+
+    #[test]
+    fn set() {
+        assert_eq!(parse("set alpha=0.8"), Request::SetAlpha(0.8));
+        assert_eq!(parse("set alpha=1"), Request::SetAlpha(1.));
+        assert_eq!(
+            parse("set color=#ff8000"),
+            Request::SetColor(Color {
+                r: 0xff,
+                g: 0x80,
+                b: 0x00
+            })
+        );
+        assert!("set alpha=1.5".parse::<Request>().is_err());
+        assert!("set alpha=-0.1".parse::<Request>().is_err());
+        assert!("set color=red".parse::<Request>().is_err());
+        assert!("set fade=2".parse::<Request>().is_err());
+        assert!("set alpha".parse::<Request>().is_err());
+        assert!("set".parse::<Request>().is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_and_extra_words() {
+        assert!("".parse::<Request>().is_err());
+        assert!("dim".parse::<Request>().is_err());
+        assert!("STATUS".parse::<Request>().is_err());
+        assert!("status now".parse::<Request>().is_err());
+        assert!("cancel 5".parse::<Request>().is_err());
+        assert!("extend 30 60".parse::<Request>().is_err());
+        assert!("set alpha=0.5 color=#000000".parse::<Request>().is_err());
+    }
+}
@@ -1,10 +1,16 @@
-use std::{collections::HashMap, time::Instant};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
 
-use log::{debug, warn};
+use anyhow::{bail, Result};
+use log::{debug, info, warn};
+use serde::Serialize;
 use smithay_client_toolkit::{
-    compositor::{CompositorHandler, CompositorState, Region},
+    compositor::{CompositorHandler, CompositorState},
     delegate_compositor, delegate_keyboard, delegate_layer, delegate_output, delegate_pointer,
     delegate_registry, delegate_seat, delegate_shm, delegate_simple, delegate_touch,
+    delegate_xdg_shell, delegate_xdg_window,
     output::{OutputHandler, OutputState},
     reexports::{
         client::{
@@ -13,151 +19,984 @@ use smithay_client_toolkit::{
                 wl_buffer::{self, WlBuffer},
                 wl_keyboard,
                 wl_output::WlOutput,
-                wl_pointer, wl_touch,
+                wl_pointer,
+                wl_seat::WlSeat,
+                wl_surface::WlSurface,
+                wl_touch,
             },
-            Connection, Dispatch, QueueHandle,
+            Connection, Dispatch, Proxy, QueueHandle,
         },
+        protocols::ext::idle_notify::v1::client::ext_idle_notifier_v1::ExtIdleNotifierV1,
         protocols::wp::{
+            pointer_gestures::zv1::client::zwp_pointer_gestures_v1::ZwpPointerGesturesV1,
             single_pixel_buffer::v1::client::wp_single_pixel_buffer_manager_v1::{
                 self, WpSinglePixelBufferManagerV1,
             },
+            tablet::zv2::client::zwp_tablet_manager_v2::ZwpTabletManagerV2,
             viewporter::client::{
                 wp_viewport::{self, WpViewport},
-                wp_viewporter::{self, WpViewporter},
+                wp_viewporter::WpViewporter,
+            },
+        },
+        protocols_wlr::{
+            foreign_toplevel::v1::client::{
+                zwlr_foreign_toplevel_handle_v1::ZwlrForeignToplevelHandleV1,
+                zwlr_foreign_toplevel_manager_v1::ZwlrForeignToplevelManagerV1,
             },
+            gamma_control::v1::client::zwlr_gamma_control_manager_v1::ZwlrGammaControlManagerV1,
+            output_power_management::v1::client::zwlr_output_power_manager_v1::ZwlrOutputPowerManagerV1,
         },
     },
     registry::{ProvidesRegistryState, RegistryState, SimpleGlobal},
     registry_handlers,
     seat::{
-        keyboard::KeyboardHandler,
-        pointer::{PointerEvent, PointerEventKind, PointerHandler},
-        touch::TouchHandler,
+        keyboard::Keysym,
+        keyboard::{KeyboardData, KeyboardHandler},
+        pointer::{PointerData, PointerEvent, PointerEventKind, PointerHandler},
+        touch::{TouchData, TouchHandler},
         Capability, SeatHandler, SeatState,
     },
     shell::{
-        wlr_layer::{KeyboardInteractivity, Layer, LayerShell, LayerShellHandler, LayerSurface},
+        wlr_layer::{LayerShellHandler, LayerSurface},
+        xdg::window::{Window, WindowConfigure, WindowHandler},
         WaylandSurface,
     },
-    shm::{slot::SlotPool, Shm, ShmHandler},
+    shm::{Shm, ShmHandler},
 };
 
 use crate::{
-    buffer::{BufferManager, BufferType},
-    DimOpts, DimSurface,
+    backend::{DimBackend, Output},
+    backlight::{self, Backlight},
+    buffer::{BufferManager, Color},
+    consts::{INHIBIT_GRACE, PASSTHROUGH_IDLE_TIMEOUT_MS, STEP_INTERVAL},
+    control::{Controlled, Request},
+    events::Event,
+    gamma::Gamma,
+    gestures::PointerGestures,
+    handoff::Handoff,
+    hooks::Report,
+    idle::IdleWatch,
+    inhibit,
+    mpris::Mpris,
+    overlay::{Overlay, Shell},
+    power::PoweredOff,
+    screensaver::ScreenSaver,
+    seat::SeatDevices,
+    state::{DimState, Status},
+    toplevel::{Exemptions, ToplevelData, ToplevelState},
+    Backend, DimCommand, DimOpts, DimOutcome, InhibitPolicy,
 };
 
 pub struct DimData {
-    compositor: CompositorState,
     registry_state: RegistryState,
     seat_state: SeatState,
     output_state: OutputState,
-    layer_shell: LayerShell,
-    buffer_mgr: BufferManager,
-    viewporter: SimpleGlobal<WpViewporter, 1>,
-
-    alpha: f32,
-    passthrough: bool,
-    start_time: Instant,
-    fade_sec: f32,
-    fade_done: bool,
-    surfaces: HashMap<WlOutput, DimSurface>,
-
-    keyboard: Option<wl_keyboard::WlKeyboard>,
-    pointer: Option<wl_pointer::WlPointer>,
-    touch: Option<wl_touch::WlTouch>,
-    exit: bool,
+    tablet_manager: Option<SimpleGlobal<ZwpTabletManagerV2, 1>>,
+    pointer_gestures: Option<SimpleGlobal<ZwpPointerGesturesV1, 3>>,
+    idle_notifier: Option<SimpleGlobal<ExtIdleNotifierV1, 2>>,
+
+    state: DimState,
+    dimming: bool,
+    /// Locker we handed off to once the dim completed, waited for to lock the session
+    handoff: Option<Handoff>,
+    /// Kept apart from the other backends, as the compositor tells us about its surfaces
+    overlay: Option<Overlay>,
+    backends: Vec<Slot>,
+    /// Outputs given to the backends for the current dim
+    outputs: Vec<WlOutput>,
+
+    seats: HashMap<WlSeat, SeatDevices>,
+    allowed_seats: Vec<String>,
+    /// Whether we warned that no seat is one we listen to
+    warned_no_seat: bool,
+    idle_watches: Vec<IdleWatch>,
+    mode: Mode,
+    /// Policy towards idle inhibitors of the compositor, ignoring them unless we check them
+    on_inhibit: InhibitPolicy,
+    /// Since when all seats are idle of input, while we wait for them to be idle as well
+    inhibit_check_since: Option<Instant>,
+
+    exemptions: Exemptions,
+    toplevels: Vec<ZwlrForeignToplevelHandleV1>,
+
+    inhibit_records: bool,
+    mpris: Option<Mpris>,
+    screensaver: Option<ScreenSaver>,
+    /// Whether inhibitors we poll end the dim, rather than being waited out
+    exit_on_inhibitor: bool,
+    /// Whether to start the dim on the next poll, see [`DimData::poll_inhibitors`]
+    inhibitor_wait: bool,
+
+    /// How the overlay is shown, told as we begin
+    shell_name: &'static str,
+    /// Whether a dim started since [`DimData::take_started`] was last called
+    started: bool,
+    report: Report,
+
+    /// Locker to hand off to once the dim completes, see [`Handoff`]
+    then: Option<String>,
+    then_delay: Duration,
+    then_opaque: bool,
+    /// Whether to turn outputs off once the dim completes, until the user returns
+    power_off: bool,
+    power_manager: Option<SimpleGlobal<ZwlrOutputPowerManagerV1, 1>>,
+    /// Outputs turned off as the last dim completed, outliving it until the user returns
+    powered_off: Option<PoweredOff>,
+    /// Kept to make requests where we are not handed one, e.g. once the dim completes
+    qh: QueueHandle<Self>,
+}
+
+/// A backend added with [`DimData::add_backend`]
+struct Slot {
+    backend: Box<dyn DimBackend>,
+    /// Whether the backend is prepared for the current dim
+    ready: bool,
+}
+
+/// What we were started to do, see [`DimCommand`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Dim,
+    Daemon,
+    WaitIdle,
+    WaitActive,
+}
+
+/// The kinds of user input which will cancel the dim
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InputClass {
+    Keyboard,
+    Pointer,
+    Touch,
+    Tablet,
+    /// Touchpad swipes, pinches and holds
+    Gesture,
+    /// Any activity reported by the compositor, e.g. while we pass input through
+    Activity,
+}
+
+impl InputClass {
+    pub fn name(&self) -> &'static str {
+        match self {
+            InputClass::Keyboard => "keyboard",
+            InputClass::Pointer => "pointer",
+            InputClass::Touch => "touch",
+            InputClass::Tablet => "tablet",
+            InputClass::Gesture => "gesture",
+            InputClass::Activity => "activity",
+        }
+    }
 }
 
 impl DimData {
-    /// Generate a new instance of our app
+    /// Generate a new instance of our app, failing if the compositor could not tell us what the
+    /// dim needs to end.
     pub fn new(
         compositor: CompositorState,
         globals: &GlobalList,
         qh: &QueueHandle<Self>,
-        layer_shell: LayerShell,
+        shell: Shell,
         opts: DimOpts,
-    ) -> Self {
-        let buffer_mgr = match SimpleGlobal::<WpSinglePixelBufferManagerV1, 1>::bind(globals, qh) {
-            Ok(sg) => BufferManager::SinglePixel(sg),
-            Err(_) => {
-                warn!("Single pixel buffer not available! Using fallback.");
-
-                let shm = Shm::bind(globals, qh).expect("Could not create shm.");
-                let pool = SlotPool::new(1, &shm).expect("Failed to create pool!");
-                BufferManager::Shm(shm, pool)
+    ) -> Result<Self> {
+        let tablet_manager = SimpleGlobal::<ZwpTabletManagerV2, 1>::bind(globals, qh)
+            .inspect_err(|_| debug!("Tablet manager not available, ignoring tablets."))
+            .ok();
+
+        let pointer_gestures = SimpleGlobal::<ZwpPointerGesturesV1, 3>::bind(globals, qh)
+            .inspect_err(|_| debug!("Pointer gestures not available, ignoring gestures."))
+            .ok();
+
+        let shell_name = shell.name();
+        let backends = opts.backends();
+        let overlay = backends
+            .contains(&Backend::Overlay)
+            .then(|| Overlay::new(compositor, shell, globals, qh, opts.passthrough));
+        let backlight_root = backends
+            .contains(&Backend::Backlight)
+            .then(|| opts.backlight_root());
+        let receives_input = overlay
+            .as_ref()
+            .is_some_and(|overlay| overlay.capabilities().input);
+
+        let idle_ms = |secs: u64| u32::try_from(secs * 1000).unwrap_or(u32::MAX);
+        let input_watch = IdleWatch::Input(PASSTHROUGH_IDLE_TIMEOUT_MS);
+        let (mode, idle_watches) = match opts.command {
+            Some(DimCommand::Daemon(_)) => (
+                Mode::Daemon,
+                vec![IdleWatch::Idle(idle_ms(opts.daemon.idle()))],
+            ),
+            Some(DimCommand::WaitIdle { seconds }) => {
+                (Mode::WaitIdle, vec![IdleWatch::Idle(idle_ms(seconds))])
+            }
+            Some(DimCommand::WaitActive) => (Mode::WaitActive, vec![input_watch]),
+            Some(
+                DimCommand::Inhibit { .. } | DimCommand::Ctl { .. } | DimCommand::Doctor { .. },
+            ) => {
+                bail!("Clients of other instances do not dim")
+            }
+            // comparing input idleness with idleness respecting inhibitors tells us whether
+            // there are any active inhibitors
+            None if opts.checks_idle_inhibitors() => (
+                Mode::Dim,
+                vec![input_watch, IdleWatch::Idle(PASSTHROUGH_IDLE_TIMEOUT_MS)],
+            ),
+            // without an overlay taking input, e.g. while passing it through, or while outputs are
+            // off, we ask the compositor about activity instead
+            None if opts.power_off_on_timeout || !receives_input => (Mode::Dim, vec![input_watch]),
+            None => (Mode::Dim, vec![]),
+        };
+
+        let idle_notifier = if idle_watches.is_empty() {
+            None
+        } else {
+            SimpleGlobal::<ExtIdleNotifierV1, 2>::bind(globals, qh)
+                .inspect_err(|_| warn!("Idle notifier not available!"))
+                .ok()
+        };
+
+        // before version 2, activity is only told through notifications respecting inhibitors
+        let notifier_v1 = idle_notifier
+            .as_ref()
+            .and_then(|sg| sg.get().ok())
+            .is_some_and(|notifier| notifier.version() < 2);
+        match mode {
+            Mode::Dim if notifier_v1 && !receives_input => {
+                bail!("Idle notifier is older than version 2, activity could not end a dim taking no input while an idle inhibitor is active.");
+            }
+            Mode::WaitActive if notifier_v1 => {
+                bail!("Idle notifier is older than version 2, activity could not be noticed while an idle inhibitor is active.");
             }
+            // idleness respecting inhibitors is all the other modes ask about
+            _ => {}
+        }
+
+        let seat_state = SeatState::new(globals, qh);
+        // seats present at startup do not go through SeatHandler::new_seat
+        let seats = seat_state
+            .seats()
+            .map(|seat| {
+                let devices = SeatDevices::new(
+                    tablet_manager.as_ref().and_then(|sg| sg.get().ok()),
+                    idle_notifier
+                        .as_ref()
+                        .and_then(|sg| sg.get().ok())
+                        .map(|notifier| (notifier, idle_watches.as_slice())),
+                    &seat,
+                    qh,
+                );
+                (seat, devices)
+            })
+            .collect::<HashMap<_, _>>();
+
+        // without an idle notifier or seats we will never hear whether there are inhibitors
+        let on_inhibit = match opts.on_inhibit() {
+            _ if idle_notifier.is_none() || seats.is_empty() => InhibitPolicy::Ignore,
+            policy => policy,
+        };
+        if mode == Mode::Dim && !receives_input && idle_notifier.is_none() {
+            warn!("Only the timeout will end the dim, as it receives no input.");
+        }
+
+        // playing media is an inhibitor we have to ask about ourselves
+        let mpris = if opts.mpris
+            && opts.on_inhibit() != InhibitPolicy::Ignore
+            && matches!(mode, Mode::Dim | Mode::Daemon)
+        {
+            Mpris::connect(opts.ignore_players.clone())
+                .inspect_err(|e| warn!("Not watching media players: {e}"))
+                .ok()
+        } else {
+            None
+        };
+        let screensaver = if mode == Mode::Daemon && opts.daemon.screensaver {
+            ScreenSaver::serve()
+                .inspect_err(|e| warn!("Not serving org.freedesktop.ScreenSaver: {e}"))
+                .ok()
+        } else {
+            None
+        };
+        // as held by `dim inhibit`
+        let inhibit_records =
+            matches!(mode, Mode::Dim | Mode::Daemon) && opts.on_inhibit() != InhibitPolicy::Ignore;
+        let exit_on_inhibitor = mode == Mode::Dim && opts.on_inhibit() == InhibitPolicy::Exit;
+
+        let exemptions = Exemptions {
+            skip_if_fullscreen: opts.skip_if_fullscreen,
+            app_ids: opts.exempt_app_ids.clone(),
+        };
+        // the initial toplevels are announced right away, before any of our surfaces are
+        // configured, so we will know whether to skip dimming before drawing anything
+        let wants_toplevels = matches!(mode, Mode::Dim | Mode::Daemon) && !exemptions.is_empty();
+        if wants_toplevels
+            && globals
+                .bind::<ZwlrForeignToplevelManagerV1, _, _>(qh, 1..=3, ())
+                .is_err()
+        {
+            warn!("Foreign toplevel manager not available, ignoring exemptions.");
+        }
+
+        let then_delay = Duration::from_secs_f32(opts.then_delay());
+
+        // only the compositor can tell us the user returned while outputs are off
+        let power_manager = if opts.power_off_on_timeout && matches!(mode, Mode::Dim | Mode::Daemon)
+        {
+            SimpleGlobal::<ZwlrOutputPowerManagerV1, 1>::bind(globals, qh)
+                .inspect_err(|_| {
+                    warn!("Output power manager not available, not turning outputs off.")
+                })
+                .ok()
+        } else {
+            None
         };
+        let power_off = power_manager.is_some() && idle_notifier.is_some();
+        if power_manager.is_some() && !power_off {
+            warn!("Not turning outputs off without an idle notifier to turn them back on.");
+        }
 
-        Self {
-            compositor,
+        let mut data = Self {
             registry_state: RegistryState::new(globals),
-            seat_state: SeatState::new(globals, qh),
+            seat_state,
             output_state: OutputState::new(globals, qh),
-            layer_shell,
-            buffer_mgr,
-            viewporter: SimpleGlobal::<wp_viewporter::WpViewporter, 1>::bind(globals, qh)
-                .expect("wp_viewporter not available"),
+            tablet_manager,
+            pointer_gestures,
+            idle_notifier,
+
+            state: DimState::new(&opts),
+            handoff: None,
+            // started below if we need not wait for anything
+            dimming: false,
+            overlay,
+            backends: Vec::new(),
+            outputs: Vec::new(),
+
+            seats,
+            allowed_seats: opts.seats,
+            warned_no_seat: false,
+            idle_watches,
+            mode,
+            on_inhibit,
+            inhibit_check_since: None,
+
+            exemptions,
+            toplevels: Vec::new(),
+
+            // set once the next poll should start the dim, see DimData::dim_when_ready
+            inhibitor_wait: false,
+            exit_on_inhibitor,
+            inhibit_records,
+            mpris,
+            screensaver,
+
+            shell_name,
+            started: false,
+            report: Report::default(),
+
+            then_delay,
+            then_opaque: opts.then_opaque,
+            then: opts.then,
+            power_off,
+            power_manager,
+            powered_off: None,
+            qh: qh.clone(),
+        };
 
-            alpha: opts.alpha(),
-            passthrough: opts.passthrough,
-            start_time: Instant::now(),
-            fade_sec: opts.fade(),
-            fade_done: false,
-            surfaces: HashMap::new(),
+        if let Some(root) = backlight_root {
+            // brightness left behind by a crashed instance should not wait for our first dim
+            backlight::recover_leftovers();
+            data.add_backend(Backlight::new(root));
+        }
+        if backends.contains(&Backend::Gamma) {
+            match Gamma::bind(globals, qh) {
+                Ok(gamma) => data.add_backend(gamma),
+                Err(_) => warn!("Gamma control manager not available, not dimming gamma."),
+            }
+        }
+
+        Ok(data)
+    }
+
+    /// Tell we are connected, then dim as soon as we may. Called once, after any backends and
+    /// event callback are given, so they are part of the first dim.
+    pub fn begin(&mut self) {
+        self.emit(Event::Connected {
+            backend: self.shell_name,
+        });
+        self.dim_when_ready();
+    }
+
+    /// Dim right away if nothing need be waited for. Other modes only dim once idle if at all,
+    /// and we wait to hear about inhibitors if we care for them.
+    pub(crate) fn dim_when_ready(&mut self) {
+        if self.mode != Mode::Dim {
+            return;
+        }
 
-            keyboard: None,
-            pointer: None,
-            touch: None,
-            exit: false,
+        match self.on_inhibit {
+            InhibitPolicy::Ignore if self.polls_inhibitors() => self.inhibitor_wait = true,
+            // outputs are dimmed as they are announced
+            InhibitPolicy::Ignore => self.start(),
+            // the compositor tells us once seats are idle, which they may already be after a dim
+            _ if !self.seats.is_empty() && self.all_seats_idle(false) => self.start(),
+            // nothing tells us about input while inhibited before version 2, so only the grace
+            // passing without the seats idling does
+            _ if !self.tells_input() => {
+                self.inhibit_check_since.get_or_insert_with(Instant::now);
+            }
+            _ => {}
         }
     }
 
-    pub fn should_exit(&self) -> bool {
-        self.exit
+    /// Dim with the given backend as well, from the next dim on.
+    pub fn add_backend(&mut self, backend: impl DimBackend + 'static) {
+        self.add_boxed_backend(Box::new(backend));
     }
 
-    /// Create a new dimmed surface to show on the given output
-    fn new_surface(
-        &self,
-        qh: &QueueHandle<Self>,
-        buffer: BufferType,
-        back_buffer: BufferType,
-        output: &WlOutput,
-    ) -> DimSurface {
-        let layer = self.layer_shell.create_layer_surface(
-            qh,
-            self.compositor.create_surface(qh),
-            Layer::Overlay,
-            Some("dim_layer"),
-            Some(output),
-        );
+    pub(crate) fn add_boxed_backend(&mut self, backend: Box<dyn DimBackend>) {
+        if self.state.color != Color::default() && !backend.capabilities().color {
+            info!("The {} backend does not show the color.", backend.name());
+        }
 
-        let (width, height) = self
-            .output_state
-            .info(output)
-            .and_then(|info| info.logical_size)
-            .unwrap_or((1920, 1080)); // no info for this output yet, default size
+        self.backends.push(Slot {
+            backend,
+            ready: false,
+        });
+    }
 
-        if self.passthrough {
-            let input_region = Region::new(&self.compositor).expect("Failed to get a wl_region");
-            layer.set_keyboard_interactivity(KeyboardInteractivity::None);
-            layer.set_input_region(Some(input_region.wl_region()));
-        } else {
-            layer.set_keyboard_interactivity(KeyboardInteractivity::Exclusive);
+    /// Call the given function with every event, as well as printing them if asked to.
+    pub fn on_event(&mut self, callback: impl Fn(&Event) + 'static) {
+        self.state.on_event = Some(Box::new(callback));
+    }
+
+    /// Backends dimming the current dim, the overlay first
+    fn backends_mut(&mut self) -> impl Iterator<Item = &mut (dyn DimBackend + 'static)> {
+        let overlay = self
+            .overlay
+            .iter_mut()
+            .map(|overlay| overlay as &mut (dyn DimBackend + 'static));
+        let others = self
+            .backends
+            .iter_mut()
+            .filter(|slot| slot.ready)
+            .map(|slot| slot.backend.as_mut());
+        overlay.chain(others)
+    }
+
+    fn emit(&self, event: Event) {
+        self.state.emit(event);
+    }
+
+    /// Tell how much time is left, if we are dimming and printing events.
+    pub fn tick(&self) {
+        if self.running() {
+            self.emit(Event::Tick {
+                remaining: self.time_left().map(|left| left.as_secs_f32()),
+            });
+        }
+    }
+
+    /// Whether the compositor is able to tell us about idleness and activity
+    pub fn has_idle_notifier(&self) -> bool {
+        self.idle_notifier.is_some()
+    }
+
+    /// Whether the compositor tells us about input regardless of idle inhibitors
+    fn tells_input(&self) -> bool {
+        self.idle_notifier
+            .as_ref()
+            .and_then(|sg| sg.get().ok())
+            .is_some_and(|notifier| notifier.version() >= 2)
+    }
+
+    /// Whether we need [`DimData::poll_inhibitors`] to be called every
+    /// [`INHIBITOR_POLL_INTERVAL`](crate::consts::INHIBITOR_POLL_INTERVAL)
+    pub fn polls_inhibitors(&self) -> bool {
+        self.inhibit_records || self.mpris.is_some() || self.screensaver.is_some()
+    }
+
+    /// Dim every output with every backend, starting the fade and timer from now.
+    pub fn start(&mut self) {
+        self.state.outcome = None;
+
+        if self.exempt_toplevel_focused() {
+            debug!("Exempted toplevel focused, not dimming");
+            self.state.outcome = Some(DimOutcome::Exempted);
+            return;
+        }
+
+        if self.polled_inhibitor_active() {
+            if self.exit_on_inhibitor {
+                self.state.outcome = Some(DimOutcome::Inhibited);
+            } else {
+                info!("Dimming is inhibited, waiting for inhibitors to clear...");
+                self.inhibitor_wait = true;
+            }
+            return;
+        }
+
+        debug!("Starting dim");
+        self.dimming = true;
+        if let Some(screensaver) = &self.screensaver {
+            screensaver.set_active(true);
+        }
+
+        for slot in &mut self.backends {
+            slot.ready = slot
+                .backend
+                .prepare()
+                .inspect_err(|e| warn!("Not dimming with {}: {e:#}", slot.backend.name()))
+                .is_ok();
+        }
+        for output in self.output_state.outputs() {
+            self.add_output(output);
+        }
+        self.started = true;
+        self.state.start();
+    }
+
+    /// Whether a dim has started since last asked, for [`Hook::Start`](crate::hooks::Hook::Start)
+    pub fn take_started(&mut self) -> bool {
+        std::mem::take(&mut self.started)
+    }
+
+    /// What we know about the current dim, or the last one once it has ended
+    pub fn report(&self) -> Report {
+        match self.dimming {
+            true => Report {
+                outcome: self.state.outcome,
+                elapsed: self.state.timeline.elapsed(),
+                cancel_reason: self.state.cancel_reason,
+                outputs: self.output_names(),
+            },
+            false => self.report.clone(),
+        }
+    }
+
+    /// Names of the outputs we are dimming
+    fn output_names(&self) -> Vec<String> {
+        self.outputs
+            .iter()
+            .filter_map(|output| self.output_state.info(output)?.name)
+            .collect()
+    }
+
+    /// Restore what our backends dimmed, ignoring input until started again.
+    pub fn stop(&mut self) {
+        debug!("Stopping dim");
+        self.dimming = false;
+        self.state.stop();
+        // leaves the locker running
+        self.handoff = None;
+        if let Some(screensaver) = &self.screensaver {
+            screensaver.set_active(false);
+        }
+        for backend in self.backends_mut() {
+            backend.restore();
+        }
+        for slot in &mut self.backends {
+            slot.ready = false;
+        }
+        self.outputs.clear();
+    }
+
+    /// Whether the dim is showing and may still end in any way, i.e. has no outcome and is not
+    /// being ended.
+    fn running(&self) -> bool {
+        self.dimming && self.state.running() && self.handoff.is_none()
+    }
+
+    /// Time left until the dim completes, if it is running and not infinite.
+    pub fn time_left(&self) -> Option<Duration> {
+        self.state
+            .time_left()
+            .filter(|_| self.dimming && self.handoff.is_none())
+    }
+
+    /// Time left until we give up on seats becoming idle, deciding the session is inhibited.
+    fn inhibit_grace_left(&self) -> Option<Duration> {
+        self.inhibit_check_since
+            .map(|since| INHIBIT_GRACE.saturating_sub(since.elapsed()))
+    }
+
+    /// How long to wait for events at most before calling [`DimData::poll_outcome`] again.
+    pub fn dispatch_timeout(&self) -> Option<Duration> {
+        [
+            self.time_left(),
+            self.inhibit_grace_left(),
+            self.state.fade_out_left(),
+            self.handoff.as_ref().map(|handoff| handoff.poll_timeout()),
+            Some(STEP_INTERVAL).filter(|_| self.dimming && self.state.timeline.animating()),
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+    }
+
+    /// Take the outcome of the dim once it has ended, stopping it.
+    pub fn poll_outcome(&mut self) -> Option<DimOutcome> {
+        self.step();
+
+        if self.state.outcome.is_none() && self.time_left() == Some(Duration::ZERO) {
+            self.complete();
+        }
+
+        if self.handoff.as_mut().is_some_and(|handoff| handoff.done()) {
+            self.handoff = None;
+            self.time_out();
+        }
+
+        self.state.poll_fade_out();
+
+        if self.inhibit_grace_left() == Some(Duration::ZERO) {
+            self.inhibit_check_since = None;
+            match self.on_inhibit {
+                InhibitPolicy::Exit => self.state.outcome = Some(DimOutcome::Inhibited),
+                _ => info!("Session is inhibited, waiting for inhibitors to clear..."),
+            }
+        }
+
+        self.state.outcome?;
+        // the dim may have ended before it started, e.g. when an exempted app was focused
+        let report = match self.dimming {
+            true => self.report(),
+            false => Report::default(),
+        };
+        let (outcome, report) = self.state.take_outcome(report)?;
+        self.report = report;
+
+        self.stop();
+        self.emit(Event::Outcome {
+            outcome,
+            code: outcome.exit_code(),
+        });
+        Some(outcome)
+    }
+
+    /// Whether input from the given seat should be listened to
+    fn seat_allowed(&self, seat: &WlSeat) -> bool {
+        if self.allowed_seats.is_empty() {
+            return true;
+        }
+
+        let name = self.seat_state.info(seat).and_then(|info| info.name);
+        name.is_some_and(|n| self.allowed_seats.contains(&n))
+    }
+
+    /// Called whenever any user input is detected on the given seat, making us exit if the seat
+    /// is one we listen to.
+    pub(crate) fn cancel(&mut self, seat: &WlSeat, class: InputClass) {
+        self.cancel_with_key(seat, class, None);
+    }
+
+    /// Like [`DimData::cancel`], telling which key was pressed if any.
+    fn cancel_with_key(&mut self, seat: &WlSeat, class: InputClass, keysym: Option<Keysym>) {
+        if self.seat_allowed(seat) {
+            self.wake();
+        }
+
+        if !self.running() {
+            return;
+        }
+
+        if !self.seat_allowed(seat) {
+            debug!("Ignoring {class:?} input from seat {}", seat.id());
+            return;
+        }
+
+        debug!("{class:?} input detected on seat {}", seat.id());
+        let keysym = keysym.map(|keysym| match keysym.name() {
+            Some(name) => name.strip_prefix("XK_").unwrap_or(name).to_owned(),
+            None => format!("{:#x}", keysym.raw()),
+        });
+        self.state.cancel(class, keysym);
+    }
+
+    /// Whether all the seats we listen to are idle, either of input or respecting inhibitors.
+    /// Without any seat to listen to, nothing tells us the session is idle.
+    fn all_seats_idle(&self, of_input: bool) -> bool {
+        let mut seats = self
+            .seats
+            .iter()
+            .filter(|(seat, _)| self.seat_allowed(seat))
+            .peekable();
+
+        seats.peek().is_some()
+            && seats.all(|(_, devices)| match of_input {
+                true => devices.input_idle,
+                false => devices.idle,
+            })
+    }
+
+    /// Warn once when none of the seats is one we listen to, as the session is then never idle
+    fn warn_no_seat(&mut self) {
+        let allowed = self.seats.keys().any(|seat| self.seat_allowed(seat));
+        if !allowed && !self.warned_no_seat {
+            warn!(
+                "No seat matches {:?}, considering the session active",
+                self.allowed_seats
+            );
+            self.warned_no_seat = true;
+        }
+    }
+
+    /// The compositor considers the given seat idle
+    pub(crate) fn seat_idled(&mut self, seat: &WlSeat, watch: IdleWatch) {
+        if let Some(devices) = self.seats.get_mut(seat) {
+            match watch {
+                IdleWatch::Input(_) => devices.input_idle = true,
+                IdleWatch::Idle(_) => devices.idle = true,
+            }
+        }
+
+        self.warn_no_seat();
+
+        // we only act once all the seats we listen to are idle
+        if !self.all_seats_idle(matches!(watch, IdleWatch::Input(_))) {
+            return;
+        }
+
+        match (self.mode, watch) {
+            (Mode::Daemon, IdleWatch::Idle(_)) if !self.dimming => self.start(),
+            (Mode::WaitIdle, IdleWatch::Idle(_)) => {
+                self.state.outcome = Some(DimOutcome::Completed)
+            }
+            // not waiting for inhibitors anymore
+            (Mode::Dim, IdleWatch::Idle(_)) if !self.dimming && self.state.outcome.is_none() => {
+                self.inhibit_check_since = None;
+                self.start();
+            }
+            // idle of input but not otherwise, there may be inhibitors
+            (Mode::Dim, IdleWatch::Input(_)) if !self.dimming && !self.all_seats_idle(false) => {
+                self.inhibit_check_since.get_or_insert_with(Instant::now);
+            }
+            _ => {}
+        }
+    }
+
+    /// The compositor has seen activity on the given seat after it was idle
+    pub(crate) fn seat_resumed(&mut self, seat: &WlSeat, watch: IdleWatch) {
+        let Some(devices) = self.seats.get_mut(seat) else {
+            return;
+        };
+
+        match watch {
+            IdleWatch::Input(_) => devices.input_idle = false,
+            IdleWatch::Idle(_) => devices.idle = false,
+        }
+
+        // input resumes input watches first, so the seat no longer being idle while still being
+        // idle of input means an inhibitor has appeared
+        let inhibited = matches!(watch, IdleWatch::Idle(_)) && devices.input_idle;
+
+        if !inhibited && self.seat_allowed(seat) {
+            self.wake();
+        }
+
+        match self.mode {
+            Mode::WaitActive if self.seat_allowed(seat) => {
+                debug!("Activity detected on seat {}", seat.id());
+                self.state.outcome = Some(DimOutcome::Completed);
+            }
+            Mode::Dim if inhibited && self.on_inhibit != InhibitPolicy::Ignore => {
+                if self.running() {
+                    debug!("Idle inhibitor appeared");
+                    self.state.outcome = Some(DimOutcome::Inhibited);
+                }
+            }
+            _ => {
+                self.inhibit_check_since = None;
+                // the user returned before inhibitors cleared
+                self.inhibitor_wait = false;
+                self.cancel(seat, InputClass::Activity);
+            }
+        }
+    }
+
+    /// Whether any of the inhibitors we keep track of ourselves is active
+    fn polled_inhibitor_active(&self) -> bool {
+        if self.inhibit_records && inhibit::held() {
+            debug!("Inhibited by `dim inhibit`");
+            return true;
+        }
+
+        if self.screensaver.as_ref().is_some_and(|s| s.inhibited()) {
+            debug!("An app is inhibiting the screensaver");
+            return true;
+        }
+
+        let Some(mpris) = &self.mpris else {
+            return false;
+        };
+
+        match mpris.playing() {
+            Ok(players) if !players.is_empty() => {
+                debug!("Media is playing on {players:?}");
+                true
+            }
+            Ok(_) => false,
+            Err(e) => {
+                warn!("Failed to ask media players whether they are playing: {e}");
+                false
+            }
+        }
+    }
+
+    /// Start the dim if we were waiting for inhibitors to clear, or end it if one appeared.
+    pub fn poll_inhibitors(&mut self) {
+        if self.inhibitor_wait {
+            self.inhibitor_wait = false;
+            self.start();
+        } else if self.running() && self.polled_inhibitor_active() {
+            debug!("Inhibitor appeared while dimming");
+            self.state.outcome = Some(DimOutcome::Inhibited);
+            // the daemon dims again once they clear, unless the user returns first
+            self.inhibitor_wait = self.mode == Mode::Daemon;
+        }
+    }
+
+    /// End the dim as if its duration passed, first handing off to the locker if we have one.
+    fn complete(&mut self) {
+        let Some(command) = &self.then else {
+            self.time_out();
+            return;
+        };
+
+        match Handoff::spawn(command, self.then_delay) {
+            Ok(handoff) => {
+                self.handoff = Some(handoff);
+                if self.then_opaque {
+                    let fade_sec = self.state.timeline.fade_sec();
+                    self.state.timeline.ramp_to(1., fade_sec);
+                }
+            }
+            Err(e) => {
+                warn!("Failed to hand off to the locker: {e:#}");
+                self.time_out();
+            }
+        }
+    }
+
+    /// End the dim now that it has completed, turning our outputs off until the user returns if
+    /// desired. Only turning them back on waits for the user, the dim ending right away.
+    fn time_out(&mut self) {
+        let manager = self.power_manager.as_ref().and_then(|sg| sg.get().ok());
+        match manager {
+            Some(manager) if self.power_off && !self.outputs.is_empty() => {
+                debug!("Turning outputs off");
+                self.powered_off = Some(PoweredOff::new(manager, &self.outputs, &self.qh));
+                self.state.outcome = Some(DimOutcome::PoweredOff);
+            }
+            _ => self.state.outcome = Some(DimOutcome::Completed),
+        }
+    }
+
+    /// The user has returned while our outputs are off, turning them back on.
+    fn wake(&mut self) {
+        if self.powered_off.take().is_some() {
+            debug!("Turning outputs back on");
+        }
+    }
+
+    /// Whether outputs turned off by the last dim are still off, as the user has not returned
+    pub fn powered_off(&self) -> bool {
+        self.powered_off.is_some()
+    }
+
+    /// Show the level of the fade by now with every backend.
+    fn step(&mut self) {
+        if !self.dimming {
+            return;
+        }
+
+        let level = self.state.step();
+        for backend in self.backends_mut() {
+            backend.apply(level);
+        }
+    }
+
+    /// Whether any exempted toplevel is currently focused
+    fn exempt_toplevel_focused(&self) -> bool {
+        self.toplevels
+            .iter()
+            .filter_map(|toplevel| toplevel.data::<ToplevelData>())
+            .any(|data| self.exemptions.exempts(&data.state()))
+    }
+
+    pub(crate) fn toplevel_added(&mut self, toplevel: ZwlrForeignToplevelHandleV1) {
+        self.toplevels.push(toplevel);
+    }
+
+    pub(crate) fn toplevel_closed(&mut self, toplevel: &ZwlrForeignToplevelHandleV1) {
+        self.toplevels.retain(|t| t != toplevel);
+    }
+
+    /// The compositor has finished describing changes to a toplevel, ending the dim if it is now
+    /// focused and exempted.
+    pub(crate) fn toplevel_changed(&mut self, toplevel: &ToplevelState) {
+        if self.running() && self.exemptions.exempts(toplevel) {
+            debug!("Exempted toplevel focused: {:?}", toplevel.app_id);
+            self.state.outcome = Some(DimOutcome::Exempted);
+        }
+    }
+
+    /// Dim the given output with every backend working per output
+    fn add_output(&mut self, output: WlOutput) {
+        let output = Output {
+            info: self.output_state.info(&output),
+            wl_output: output,
+        };
+        for backend in self.backends_mut() {
+            if backend.capabilities().per_output {
+                backend.add_output(&output);
+            }
+        }
+
+        if !self.outputs.contains(&output.wl_output) {
+            self.outputs.push(output.wl_output);
         }
+    }
 
-        layer.set_exclusive_zone(-1);
-        layer.set_size(width as _, height as _);
-        layer.commit();
+    /// Stop dimming the given output, as it is gone
+    fn remove_output(&mut self, output: &WlOutput) {
+        for backend in self.backends_mut() {
+            if backend.capabilities().per_output {
+                backend.remove_output(output);
+            }
+        }
+        self.outputs.retain(|o| o != output);
+    }
+
+    /// The compositor configured one of our surfaces, to the given size if it chose one.
+    fn configure_overlay(&mut self, surface: &WlSurface, size: Option<(u32, u32)>) {
+        // the dim is about to end, e.g. an exempted toplevel was focused before we drew anything
+        if self.state.outcome.is_some() {
+            return;
+        }
+
+        if let Some(overlay) = &mut self.overlay {
+            overlay.configure(surface, size);
+        }
+    }
+}
 
-        let viewport = self
-            .viewporter
-            .get()
-            .expect("wp_viewporter failed")
-            .get_viewport(layer.wl_surface(), qh, ());
+impl Controlled for DimData {
+    fn handle_request(&mut self, request: Request) -> Result<String> {
+        let status = match (self.running(), self.inhibitor_wait) {
+            (true, _) => Status::Dimming,
+            _ if self.handoff.is_some() => Status::Locking,
+            _ if self.powered_off.is_some() => Status::Off,
+            (false, true) => Status::Inhibited,
+            (false, false) => Status::Idle,
+        };
+        self.state.handle_request(request, status)
+    }
 
-        DimSurface::new(qh, buffer, back_buffer, viewport, layer)
+    /// Stop because of SIGTERM or SIGINT once our backends are restored, fading them out first if
+    /// desired. Being interrupted again while fading out stops right away.
+    fn interrupt(&mut self) {
+        // we will not be around to turn them on once the user returns
+        self.wake();
+        self.state.interrupt(self.running());
     }
 }
 
@@ -175,21 +1014,38 @@ impl LayerShellHandler for DimData {
     fn configure(
         &mut self,
         _conn: &smithay_client_toolkit::reexports::client::Connection,
-        qh: &QueueHandle<Self>,
+        _qh: &QueueHandle<Self>,
         layer: &LayerSurface,
         configure: smithay_client_toolkit::shell::wlr_layer::LayerSurfaceConfigure,
         _serial: u32,
     ) {
-        let view = self
-            .surfaces
-            .values_mut()
-            .find(|view| view.layer() == layer)
-            .expect("Configuring layer not in list.");
+        self.configure_overlay(layer.wl_surface(), Some(configure.new_size));
+    }
+}
 
-        let (width, height) = configure.new_size;
-        view.set_size(width as _, height as _);
+impl WindowHandler for DimData {
+    fn request_close(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _window: &Window) {
+        // e.g. through a keybinding, which is as good as any input
+        if self.running() {
+            debug!("Window closed");
+            self.state.cancel_reason = Some("close");
+            self.state.outcome = Some(DimOutcome::Cancelled);
+        }
+    }
 
-        view.draw(qh, !self.fade_done);
+    fn configure(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        window: &Window,
+        configure: WindowConfigure,
+        _serial: u32,
+    ) {
+        let size = match configure.new_size {
+            (Some(width), Some(height)) => Some((width.get(), height.get())),
+            _ => None,
+        };
+        self.configure_overlay(window.wl_surface(), size);
     }
 }
 
@@ -215,39 +1071,14 @@ impl CompositorHandler for DimData {
     fn frame(
         &mut self,
         _conn: &smithay_client_toolkit::reexports::client::Connection,
-        qh: &QueueHandle<Self>,
+        _qh: &QueueHandle<Self>,
         surface: &smithay_client_toolkit::reexports::client::protocol::wl_surface::WlSurface,
         _time: u32,
     ) {
-        let view = self
-            .surfaces
-            .values_mut()
-            .find(|view| view.layer().wl_surface() == surface)
-            .expect("Frame event received for surface we do not own.");
-
-        let elapsed_sec = self.start_time.elapsed().as_millis() as f32 / 1000.;
-
-        if !self.fade_done {
-            let alpha = (self.alpha * (elapsed_sec / self.fade_sec)).clamp(0., self.alpha);
-            match &mut self.buffer_mgr {
-                BufferManager::SinglePixel(..) => {
-                    view.set_back_buffer(self.buffer_mgr.get_buffer(qh, alpha));
-                }
-                BufferManager::Shm(_, pool) => {
-                    if let BufferType::Shared(buffer) = view.back_buffer_mut() {
-                        let canvas = buffer.canvas(pool).expect("Canvas is not drawable.");
-                        BufferManager::paint(canvas, alpha);
-                    }
-                }
-            }
-
-            if elapsed_sec > self.fade_sec {
-                self.fade_done = true;
-                debug!("Fade done!")
-            }
+        let level = self.state.level();
+        if let Some(overlay) = &mut self.overlay {
+            overlay.frame(surface, level);
         }
-
-        view.draw(qh, !self.fade_done);
     }
 
     fn surface_enter(
@@ -277,26 +1108,31 @@ impl OutputHandler for DimData {
     fn new_output(
         &mut self,
         _conn: &smithay_client_toolkit::reexports::client::Connection,
-        qh: &QueueHandle<Self>,
+        _qh: &QueueHandle<Self>,
         output: smithay_client_toolkit::reexports::client::protocol::wl_output::WlOutput,
     ) {
-        let buffer = self.buffer_mgr.get_buffer(qh, 0.);
-        let back_buffer = self.buffer_mgr.get_buffer(qh, 0.);
-        let view = self.new_surface(qh, buffer, back_buffer, &output);
-        self.surfaces.insert(output, view);
+        let info = self.output_state.info(&output);
+        let size = info.as_ref().and_then(|info| info.logical_size);
+        self.emit(Event::OutputAdded {
+            name: info.and_then(|info| info.name),
+            width: size.map(|(width, _)| width),
+            height: size.map(|(_, height)| height),
+        });
+
+        if self.dimming {
+            self.add_output(output);
+        }
     }
 
     fn update_output(
         &mut self,
         _conn: &smithay_client_toolkit::reexports::client::Connection,
-        qh: &QueueHandle<Self>,
+        _qh: &QueueHandle<Self>,
         output: smithay_client_toolkit::reexports::client::protocol::wl_output::WlOutput,
     ) {
-        let buffer = self.buffer_mgr.get_buffer(qh, 0.);
-        let back_buffer = self.buffer_mgr.get_buffer(qh, 0.);
-        let new_view = self.new_surface(qh, buffer, back_buffer, &output);
-
-        self.surfaces.insert(output, new_view);
+        if self.dimming {
+            self.add_output(output);
+        }
     }
 
     fn output_destroyed(
@@ -305,7 +1141,10 @@ impl OutputHandler for DimData {
         _qh: &QueueHandle<Self>,
         output: smithay_client_toolkit::reexports::client::protocol::wl_output::WlOutput,
     ) {
-        self.surfaces.remove(&output);
+        self.emit(Event::OutputRemoved {
+            name: self.output_state.info(&output).and_then(|info| info.name),
+        });
+        self.remove_output(&output);
     }
 }
 
@@ -317,9 +1156,19 @@ impl SeatHandler for DimData {
     fn new_seat(
         &mut self,
         _conn: &smithay_client_toolkit::reexports::client::Connection,
-        _qh: &QueueHandle<Self>,
-        _seat: smithay_client_toolkit::reexports::client::protocol::wl_seat::WlSeat,
+        qh: &QueueHandle<Self>,
+        seat: smithay_client_toolkit::reexports::client::protocol::wl_seat::WlSeat,
     ) {
+        let devices = SeatDevices::new(
+            self.tablet_manager.as_ref().and_then(|sg| sg.get().ok()),
+            self.idle_notifier
+                .as_ref()
+                .and_then(|sg| sg.get().ok())
+                .map(|notifier| (notifier, self.idle_watches.as_slice())),
+            &seat,
+            qh,
+        );
+        self.seats.insert(seat, devices);
     }
 
     fn new_capability(
@@ -329,27 +1178,39 @@ impl SeatHandler for DimData {
         seat: smithay_client_toolkit::reexports::client::protocol::wl_seat::WlSeat,
         capability: Capability,
     ) {
+        let devices = self.seats.entry(seat.clone()).or_default();
+        // a capability may be announced again, release what we hold for it first
         match capability {
             Capability::Keyboard => {
-                self.keyboard = Some(
-                    self.seat_state
-                        .get_keyboard(qh, &seat, None)
-                        .expect("Failed to get keyboard"),
-                )
+                devices.release_keyboard();
+                match self.seat_state.get_keyboard(qh, &seat, None) {
+                    Ok(keyboard) => devices.keyboard = Some(keyboard),
+                    Err(e) => warn!("Failed to get keyboard of seat {}: {e}", seat.id()),
+                }
             }
             Capability::Pointer => {
-                self.pointer = Some(
-                    self.seat_state
-                        .get_pointer(qh, &seat)
-                        .expect("Failed to get pointer"),
-                )
+                devices.release_pointer();
+                let pointer = match self.seat_state.get_pointer(qh, &seat) {
+                    Ok(pointer) => pointer,
+                    Err(e) => {
+                        warn!("Failed to get pointer of seat {}: {e}", seat.id());
+                        return;
+                    }
+                };
+
+                devices.gestures = self
+                    .pointer_gestures
+                    .as_ref()
+                    .and_then(|sg| sg.get().ok())
+                    .map(|manager| PointerGestures::new(manager, &pointer, &seat, qh));
+                devices.pointer = Some(pointer);
             }
             Capability::Touch => {
-                self.touch = Some(
-                    self.seat_state
-                        .get_touch(qh, &seat)
-                        .expect("Failed to get touch device!"),
-                )
+                devices.release_touch();
+                match self.seat_state.get_touch(qh, &seat) {
+                    Ok(touch) => devices.touch = Some(touch),
+                    Err(e) => warn!("Failed to get touch device of seat {}: {e}", seat.id()),
+                }
             }
             _ => debug!("Unknown capability found: {capability}"),
         }
@@ -359,25 +1220,18 @@ impl SeatHandler for DimData {
         &mut self,
         _conn: &smithay_client_toolkit::reexports::client::Connection,
         _qh: &QueueHandle<Self>,
-        _seat: smithay_client_toolkit::reexports::client::protocol::wl_seat::WlSeat,
+        seat: smithay_client_toolkit::reexports::client::protocol::wl_seat::WlSeat,
         capability: Capability,
     ) {
+        let Some(devices) = self.seats.get_mut(&seat) else {
+            debug!("Capability removed from unknown seat: {capability}");
+            return;
+        };
+
         match capability {
-            Capability::Keyboard => self
-                .keyboard
-                .take()
-                .expect("Failed to remove keyboard!")
-                .release(),
-            Capability::Pointer => self
-                .pointer
-                .take()
-                .expect("Failed to remove pointer!")
-                .release(),
-            Capability::Touch => self
-                .touch
-                .take()
-                .expect("Failed to remove touch device!")
-                .release(),
+            Capability::Keyboard => devices.release_keyboard(),
+            Capability::Pointer => devices.release_pointer(),
+            Capability::Touch => devices.release_touch(),
             _ => debug!("Unknown capability removed: {capability}"),
         }
     }
@@ -386,8 +1240,10 @@ impl SeatHandler for DimData {
         &mut self,
         _conn: &smithay_client_toolkit::reexports::client::Connection,
         _qh: &QueueHandle<Self>,
-        _seat: smithay_client_toolkit::reexports::client::protocol::wl_seat::WlSeat,
+        seat: smithay_client_toolkit::reexports::client::protocol::wl_seat::WlSeat,
     ) {
+        // devices are released on drop
+        self.seats.remove(&seat);
     }
 }
 
@@ -412,18 +1268,23 @@ impl KeyboardHandler for DimData {
         _surface: &smithay_client_toolkit::reexports::client::protocol::wl_surface::WlSurface,
         _serial: u32,
     ) {
+        // a session lock takes keyboard focus from every other surface
+        if let Some(handoff) = &mut self.handoff {
+            handoff.locked();
+        }
     }
 
     fn press_key(
         &mut self,
         _conn: &smithay_client_toolkit::reexports::client::Connection,
         _qh: &QueueHandle<Self>,
-        _keyboard: &wl_keyboard::WlKeyboard,
+        keyboard: &wl_keyboard::WlKeyboard,
         _serial: u32,
-        _event: smithay_client_toolkit::seat::keyboard::KeyEvent,
+        event: smithay_client_toolkit::seat::keyboard::KeyEvent,
     ) {
-        debug!("Key pressed");
-        self.exit = true;
+        if let Some(data) = keyboard.data::<KeyboardData<Self>>() {
+            self.cancel_with_key(data.seat(), InputClass::Keyboard, Some(event.keysym));
+        }
     }
 
     fn release_key(
@@ -460,14 +1321,15 @@ impl PointerHandler for DimData {
         for e in events {
             match e.kind {
                 PointerEventKind::Enter { serial } => {
-                    if self.alpha == 1.0 {
+                    if self.state.timeline.alpha() == 1.0 {
                         pointer.set_cursor(serial, None, 0, 0);
                     }
                 }
                 PointerEventKind::Leave { .. } => {}
                 _ => {
-                    debug!("Mouse event");
-                    self.exit = true;
+                    if let Some(data) = pointer.data::<PointerData>() {
+                        self.cancel(data.seat(), InputClass::Pointer);
+                    }
                 }
             }
         }
@@ -478,14 +1340,16 @@ impl TouchHandler for DimData {
         &mut self,
         _conn: &Connection,
         _qh: &QueueHandle<Self>,
-        _touch: &wl_touch::WlTouch,
+        touch: &wl_touch::WlTouch,
         _serial: u32,
         _time: u32,
         _surface: smithay_client_toolkit::reexports::client::protocol::wl_surface::WlSurface,
         _id: i32,
         _position: (f64, f64),
     ) {
-        self.exit = true;
+        if let Some(data) = touch.data::<TouchData>() {
+            self.cancel(data.seat(), InputClass::Touch);
+        }
     }
 
     fn up(
@@ -535,8 +1399,8 @@ impl TouchHandler for DimData {
 }
 impl ShmHandler for DimData {
     fn shm_state(&mut self) -> &mut Shm {
-        match &mut self.buffer_mgr {
-            BufferManager::Shm(shm, _) => shm,
+        match self.overlay.as_mut().map(Overlay::buffer_mgr_mut) {
+            Some(BufferManager::Shm(shm, _)) => shm,
             _ => unreachable!("Attempted to call shm_state() when not using shm."),
         }
     }
@@ -545,12 +1409,19 @@ impl ShmHandler for DimData {
 delegate_compositor!(DimData);
 delegate_touch!(DimData);
 delegate_layer!(DimData);
+delegate_xdg_shell!(DimData);
+delegate_xdg_window!(DimData);
 delegate_registry!(DimData);
 delegate_pointer!(DimData);
 delegate_keyboard!(DimData);
 delegate_output!(DimData);
 delegate_seat!(DimData);
 delegate_simple!(DimData, WpViewporter, 1);
+delegate_simple!(DimData, ZwpTabletManagerV2, 1);
+delegate_simple!(DimData, ZwpPointerGesturesV1, 3);
+delegate_simple!(DimData, ExtIdleNotifierV1, 2);
+delegate_simple!(DimData, ZwlrOutputPowerManagerV1, 1);
+delegate_simple!(DimData, ZwlrGammaControlManagerV1, 1);
 delegate_shm!(DimData);
 
 impl ProvidesRegistryState for DimData {
@@ -0,0 +1,324 @@
+use anyhow::{bail, Context, Result};
+use log::warn;
+use smithay_client_toolkit::{
+    compositor::CompositorState,
+    reexports::{
+        calloop::{
+            timer::{TimeoutAction, Timer},
+            EventLoop,
+        },
+        calloop_wayland_source::WaylandSource,
+        client::{globals::registry_queue_init, Connection},
+    },
+    shell::{wlr_layer::LayerShell, xdg::XdgShell},
+};
+
+use crate::{
+    backend::DimBackend,
+    buffer::Color,
+    consts::{EVENT_TICK_INTERVAL, INHIBITOR_POLL_INTERVAL},
+    control,
+    events::{Event, EventCallback},
+    hooks::Hooks,
+    signals, Backend, DimCommand, DimData, DimOpts, DimOutcome, InhibitPolicy, Shell,
+};
+
+/// dim on the Wayland display, run from within another program rather than spawned. Made with
+/// [`Dimmer::builder`]:
+///
+/// ```no_run
+/// use dim_screen::{Dimmer, DimOutcome};
+///
+/// let mut dimmer = Dimmer::builder()
+///     .duration(10)
+///     .alpha(0.8)
+///     .on_event(|event| println!("{event:?}"))
+///     .build()?;
+///
+/// if dimmer.run()? == DimOutcome::Completed {
+///     // lock the session
+/// }
+/// # anyhow::Ok(())
+/// ```
+pub struct Dimmer {
+    data: DimData,
+    event_loop: EventLoop<'static, DimData>,
+    conn: Connection,
+    hooks: Hooks,
+    /// Whether every run dims anew, rather than waiting for the next dim of a daemon
+    dims_anew: bool,
+    ran: bool,
+}
+
+/// Options of a [`Dimmer`], defaulting to those of `dim` without arguments
+#[derive(Default)]
+pub struct DimmerBuilder {
+    opts: DimOpts,
+    hooks: Hooks,
+    listen: bool,
+    signals: bool,
+    backends: Vec<Box<dyn DimBackend>>,
+    on_event: Option<EventCallback>,
+}
+
+impl Dimmer {
+    pub fn builder() -> DimmerBuilder {
+        DimmerBuilder::default()
+    }
+
+    /// Dispatch events until the dim, or what we wait for, ends, running hooks as dims start and
+    /// end. Each run of a dim dims anew, while a daemon waits for its next dim.
+    ///
+    /// Outputs turned off as a dim ends with [`DimOutcome::PoweredOff`] stay off until the user
+    /// returns, which is only noticed while running, see [`Dimmer::wait_powered_on`].
+    pub fn run(&mut self) -> Result<DimOutcome> {
+        if std::mem::replace(&mut self.ran, true) && self.dims_anew {
+            self.data.dim_when_ready();
+        }
+
+        loop {
+            self.event_loop
+                .dispatch(self.data.dispatch_timeout(), &mut self.data)
+                .context("Failed to dispatch events!")?;
+
+            if self.data.take_started() {
+                self.hooks.started(&self.data.report());
+            }
+
+            if let Some(outcome) = self.data.poll_outcome() {
+                // requests made outside of dispatching, e.g. turning outputs back on, are
+                // otherwise only sent on the next dispatch, which may never come
+                if let Err(e) = self.conn.flush() {
+                    warn!("Failed to flush requests: {e}");
+                }
+                self.hooks.ended(&self.data.report());
+                return Ok(outcome);
+            }
+        }
+    }
+
+    /// Dispatch events until outputs turned off by the last dim are back on, as the user has
+    /// returned.
+    pub fn wait_powered_on(&mut self) -> Result<()> {
+        while self.data.powered_off() {
+            self.event_loop
+                .dispatch(None, &mut self.data)
+                .context("Failed to dispatch events!")?;
+        }
+
+        self.conn.flush().context("Failed to turn outputs back on")
+    }
+
+    pub fn data(&self) -> &DimData {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut DimData {
+        &mut self.data
+    }
+}
+
+impl DimmerBuilder {
+    /// Start from the given options, e.g. read from a config file. Options set before are
+    /// replaced.
+    pub fn opts(mut self, opts: DimOpts) -> Self {
+        self.opts = opts;
+        self
+    }
+
+    /// Seconds to dim for, 0 being infinite
+    pub fn duration(mut self, secs: u64) -> Self {
+        self.opts.duration = Some(secs);
+        self
+    }
+
+    /// How dim to get, 0.0 not at all and 1.0 fully
+    pub fn alpha(mut self, alpha: f32) -> Self {
+        self.opts.alpha = Some(alpha);
+        self
+    }
+
+    /// Seconds to fade in over
+    pub fn fade(mut self, secs: f32) -> Self {
+        self.opts.fade = Some(secs);
+        self
+    }
+
+    /// Seconds to fade out over when interrupted
+    pub fn fade_out(mut self, secs: f32) -> Self {
+        self.opts.fade_out = Some(secs);
+        self
+    }
+
+    pub fn color(mut self, color: Color) -> Self {
+        self.opts.color = Some(color);
+        self
+    }
+
+    /// Let input through to the windows below, only watching for activity
+    pub fn passthrough(mut self, passthrough: bool) -> Self {
+        self.opts.passthrough = passthrough;
+        self
+    }
+
+    /// Ways of dimming to use, see [`DimmerBuilder::backend`] for others
+    pub fn backends(mut self, backends: Vec<Backend>) -> Self {
+        self.opts.backends = backends;
+        self
+    }
+
+    pub fn on_inhibit(mut self, policy: InhibitPolicy) -> Self {
+        self.opts.on_inhibit = Some(policy);
+        self
+    }
+
+    /// Wait for something else than a dim, e.g. [`DimCommand::WaitIdle`], or dim whenever the
+    /// session is idle with [`DimCommand::Daemon`]. Other commands fail to build.
+    pub fn command(mut self, command: DimCommand) -> Self {
+        self.opts.command = Some(command);
+        self
+    }
+
+    /// Dim with the given backend as well
+    pub fn backend(mut self, backend: impl DimBackend + 'static) -> Self {
+        self.backends.push(Box::new(backend));
+        self
+    }
+
+    /// Run the given hooks as dims start and end
+    pub fn hooks(mut self, hooks: Hooks) -> Self {
+        self.hooks = hooks;
+        self
+    }
+
+    /// Call the given function with every event as it happens, e.g. sending it down a channel
+    pub fn on_event(mut self, callback: impl Fn(&Event) + 'static) -> Self {
+        self.on_event = Some(Box::new(callback));
+        self
+    }
+
+    /// Take requests through the control socket of the display, see [`control`]
+    pub fn listen(mut self, listen: bool) -> Self {
+        self.listen = listen;
+        self
+    }
+
+    /// Handle signals as [`signals::listen`] does, which blocks them on this thread and the
+    /// threads it spawns from then on
+    pub fn signals(mut self, signals: bool) -> Self {
+        self.signals = signals;
+        self
+    }
+
+    /// Connect to the Wayland display, getting ready to run.
+    pub fn build(self) -> Result<Dimmer> {
+        let opts = self.opts;
+        opts.validate()?;
+        if matches!(
+            opts.command,
+            Some(DimCommand::Inhibit { .. } | DimCommand::Ctl { .. } | DimCommand::Doctor { .. })
+        ) {
+            bail!("Only dims, daemons and waits for idleness or activity can be built");
+        }
+
+        let conn = Connection::connect_to_env().context("Failed to connect to environment")?;
+
+        let (globals, event_queue) =
+            registry_queue_init(&conn).context("Failed to initialize registry")?;
+        let qh = event_queue.handle();
+
+        let compositor =
+            CompositorState::bind(&globals, &qh).context("Compositor not available")?;
+        let shell = match LayerShell::bind(&globals, &qh) {
+            Ok(layer_shell) => Shell::Layer(layer_shell),
+            Err(_) => {
+                warn!("Layer shell not available, falling back to fullscreen windows.");
+                Shell::Xdg(
+                    XdgShell::bind(&globals, &qh)
+                        .context("Neither layer shell nor xdg shell available")?,
+                )
+            }
+        };
+
+        let event_loop = EventLoop::try_new().context("Failed to create event loop")?;
+        if self.signals {
+            // before D-Bus or the control socket spawn threads, which would otherwise receive
+            // signals
+            signals::listen(&event_loop.handle(), opts.extend_by())
+                .context("Failed to listen for signals")?;
+        }
+
+        let ticks = opts.events.is_some() || self.on_event.is_some();
+        let dims_anew = opts.command.is_none();
+        let mut data = DimData::new(compositor, &globals, &qh, shell, opts)?;
+        for backend in self.backends {
+            data.add_boxed_backend(backend);
+        }
+        if let Some(callback) = self.on_event {
+            data.on_event(callback);
+        }
+        data.begin();
+
+        if self.listen {
+            if let Err(e) = control::listen(&event_loop.handle()) {
+                warn!("Not listening for requests: {e}");
+            }
+        }
+
+        if data.polls_inhibitors() {
+            event_loop
+                .handle()
+                .insert_source(Timer::immediate(), |_, _, data: &mut DimData| {
+                    data.poll_inhibitors();
+                    TimeoutAction::ToDuration(INHIBITOR_POLL_INTERVAL)
+                })
+                .map_err(|e| e.error)
+                .context("Failed to insert inhibitor timer")?;
+        }
+
+        if ticks {
+            event_loop
+                .handle()
+                .insert_source(
+                    Timer::from_duration(EVENT_TICK_INTERVAL),
+                    |_, _, data: &mut DimData| {
+                        data.tick();
+                        TimeoutAction::ToDuration(EVENT_TICK_INTERVAL)
+                    },
+                )
+                .map_err(|e| e.error)
+                .context("Failed to insert event tick timer")?;
+        }
+
+        WaylandSource::new(conn.clone(), event_queue)
+            .insert(event_loop.handle())
+            .map_err(|e| e.error)
+            .context("Failed to insert Wayland source")?;
+
+        Ok(Dimmer {
+            data,
+            event_loop,
+            conn,
+            hooks: self.hooks,
+            dims_anew,
+            ran: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Dimmer;
+    use crate::DimCommand;
+
+    #[test]
+    fn refuses_commands_which_do_not_dim() {
+        let command = DimCommand::Ctl {
+            request: vec!["status".to_owned()],
+        };
+        let Err(e) = Dimmer::builder().command(command).build() else {
+            panic!("Built a dimmer for `dim ctl`");
+        };
+        assert!(e.to_string().starts_with("Only dims"), "{e}");
+    }
+}
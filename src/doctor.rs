@@ -0,0 +1,375 @@
+use std::fmt::Write as _;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use smithay_client_toolkit::{
+    delegate_output, delegate_registry, delegate_seat, delegate_shm,
+    output::{OutputHandler, OutputState},
+    reexports::client::{
+        globals::{registry_queue_init, Global},
+        protocol::{wl_output::WlOutput, wl_seat::WlSeat},
+        Connection, QueueHandle,
+    },
+    registry::{ProvidesRegistryState, RegistryState},
+    registry_handlers,
+    seat::{Capability, SeatHandler, SeatState},
+    shm::{Shm, ShmHandler},
+};
+
+use crate::DoctorFormat;
+
+/// Globals dim may use, in the order they are reported
+const INTERFACES: &[&str] = &[
+    "wl_compositor",
+    "wl_shm",
+    "wl_seat",
+    "wl_output",
+    "zwlr_layer_shell_v1",
+    "xdg_wm_base",
+    "wp_viewporter",
+    "wp_single_pixel_buffer_manager_v1",
+    "ext_idle_notifier_v1",
+    "zwlr_output_power_manager_v1",
+    "zwlr_gamma_control_manager_v1",
+    "zwlr_foreign_toplevel_manager_v1",
+    "zwp_tablet_manager_v2",
+    "zwp_pointer_gestures_v1",
+];
+
+/// Features needing a global of at least the given version, other than the overlay itself
+const FEATURES: &[(&str, &str, u32)] = &[
+    (
+        "daemon, wait-idle and idle inhibitors",
+        "ext_idle_notifier_v1",
+        1,
+    ),
+    (
+        "wait-active, passthrough and idle inhibitors while dimming",
+        "ext_idle_notifier_v1",
+        2,
+    ),
+    ("power-off-on-timeout", "zwlr_output_power_manager_v1", 1),
+    ("gamma backend", "zwlr_gamma_control_manager_v1", 1),
+    (
+        "skip-if-fullscreen and exempt-app-id",
+        "zwlr_foreign_toplevel_manager_v1",
+        1,
+    ),
+    ("tablet input", "zwp_tablet_manager_v2", 1),
+    ("touchpad gestures", "zwp_pointer_gestures_v1", 1),
+    ("touchpad hold gestures", "zwp_pointer_gestures_v1", 3),
+];
+
+/// What the compositor offers dim, as told by `dim doctor`
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnosis {
+    pub globals: Vec<GlobalReport>,
+    /// Pixel formats of shared memory buffers, which the fallback buffer manager uses
+    pub shm_formats: Vec<String>,
+    pub seats: Vec<SeatReport>,
+    pub outputs: Vec<OutputReport>,
+    /// How the overlay would be shown: `layer-shell`, `xdg-shell` or none
+    pub shell: Option<&'static str>,
+    /// Which buffers the overlay would draw with: `single-pixel` or `shm`
+    pub buffer_manager: &'static str,
+    pub unavailable: Vec<Unavailable>,
+}
+
+/// A global dim may use, with its highest version if announced at all
+#[derive(Debug, Clone, Serialize)]
+pub struct GlobalReport {
+    pub interface: &'static str,
+    pub version: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SeatReport {
+    pub name: Option<String>,
+    pub keyboard: bool,
+    pub pointer: bool,
+    pub touch: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OutputReport {
+    pub name: Option<String>,
+    /// Size of the current mode in pixels
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    /// Refresh rate of the current mode in Hz
+    pub refresh: Option<f32>,
+    pub scale: i32,
+}
+
+/// A feature of dim the compositor does not allow for
+#[derive(Debug, Clone, Serialize)]
+pub struct Unavailable {
+    pub feature: &'static str,
+    /// What the compositor is missing, e.g. `ext_idle_notifier_v1 version 2`
+    pub needs: String,
+}
+
+/// Connect to the compositor and find out what it offers.
+pub fn diagnose() -> Result<Diagnosis> {
+    let conn = Connection::connect_to_env().context("Failed to connect to environment")?;
+    let (globals, mut event_queue) =
+        registry_queue_init(&conn).context("Failed to initialize registry")?;
+    let qh = event_queue.handle();
+
+    let mut state = DoctorState {
+        registry_state: RegistryState::new(&globals),
+        output_state: OutputState::new(&globals, &qh),
+        seat_state: SeatState::new(&globals, &qh),
+        shm: Shm::bind(&globals, &qh).ok(),
+    };
+    // the first roundtrip binds outputs and seats, the second gets what they tell us
+    for _ in 0..2 {
+        event_queue
+            .roundtrip(&mut state)
+            .context("Failed to dispatch events!")?;
+    }
+
+    let list = globals.contents().clone_list();
+    let version = |interface: &str| {
+        list.iter()
+            .filter(|global: &&Global| global.interface == interface)
+            .map(|global| global.version)
+            .max()
+    };
+
+    let shell = match (version("zwlr_layer_shell_v1"), version("xdg_wm_base")) {
+        (Some(_), _) => Some("layer-shell"),
+        (None, Some(_)) => Some("xdg-shell"),
+        (None, None) => None,
+    };
+    let buffer_manager = match version("wp_single_pixel_buffer_manager_v1") {
+        Some(_) => "single-pixel",
+        None => "shm",
+    };
+
+    let mut unavailable = Vec::new();
+    if shell.is_none() {
+        unavailable.push(Unavailable {
+            feature: "overlay",
+            needs: "zwlr_layer_shell_v1 or xdg_wm_base".to_owned(),
+        });
+    } else if shell == Some("xdg-shell") {
+        unavailable.push(Unavailable {
+            feature: "layer shell overlay, falling back to fullscreen windows",
+            needs: "zwlr_layer_shell_v1".to_owned(),
+        });
+    }
+    if version("wp_viewporter").is_none() {
+        unavailable.push(Unavailable {
+            feature: "overlay",
+            needs: "wp_viewporter".to_owned(),
+        });
+    }
+    for &(feature, interface, min) in FEATURES {
+        if version(interface).is_none_or(|version| version < min) {
+            unavailable.push(Unavailable {
+                feature,
+                needs: match min {
+                    1 => interface.to_owned(),
+                    min => format!("{interface} version {min}"),
+                },
+            });
+        }
+    }
+    if !cfg!(feature = "dbus") {
+        unavailable.push(Unavailable {
+            feature: "mpris, screensaver and logind brightness",
+            needs: "dim built with the dbus feature".to_owned(),
+        });
+    }
+
+    Ok(Diagnosis {
+        globals: INTERFACES
+            .iter()
+            .map(|&interface| GlobalReport {
+                interface,
+                version: version(interface),
+            })
+            .collect(),
+        shm_formats: state
+            .shm
+            .as_ref()
+            .map(|shm| shm.formats().iter().map(|f| format!("{f:?}")).collect())
+            .unwrap_or_default(),
+        seats: state
+            .seat_state
+            .seats()
+            .filter_map(|seat| state.seat_state.info(&seat))
+            .map(|info| SeatReport {
+                name: info.name,
+                keyboard: info.has_keyboard,
+                pointer: info.has_pointer,
+                touch: info.has_touch,
+            })
+            .collect(),
+        outputs: state
+            .output_state
+            .outputs()
+            .filter_map(|output| state.output_state.info(&output))
+            .map(|info| {
+                let mode = info.modes.iter().find(|mode| mode.current);
+                OutputReport {
+                    name: info.name,
+                    width: mode.map(|mode| mode.dimensions.0),
+                    height: mode.map(|mode| mode.dimensions.1),
+                    refresh: mode
+                        .map(|mode| mode.refresh_rate as f32 / 1000.)
+                        .filter(|&refresh| refresh > 0.),
+                    scale: info.scale_factor,
+                }
+            })
+            .collect(),
+        shell,
+        buffer_manager,
+        unavailable,
+    })
+}
+
+impl Diagnosis {
+    /// Print this diagnosis to stdout in the given format.
+    pub fn print(&self, format: DoctorFormat) -> Result<()> {
+        match format {
+            DoctorFormat::Text => print!("{}", self.text()),
+            DoctorFormat::Json => println!("{}", serde_json::to_string_pretty(self)?),
+        }
+
+        Ok(())
+    }
+
+    fn text(&self) -> String {
+        let mut text = String::from("Globals:\n");
+        for global in &self.globals {
+            let version = match global.version {
+                Some(version) => format!("version {version}"),
+                None => "missing".to_owned(),
+            };
+            let _ = writeln!(text, "  {:<36}{version}", global.interface);
+        }
+
+        let _ = writeln!(text, "\nShm formats: {}", self.shm_formats.join(", "));
+
+        text.push_str("\nSeats:\n");
+        for seat in &self.seats {
+            let capabilities = [
+                ("keyboard", seat.keyboard),
+                ("pointer", seat.pointer),
+                ("touch", seat.touch),
+            ]
+            .into_iter()
+            .filter_map(|(name, has)| has.then_some(name))
+            .collect::<Vec<_>>();
+            let _ = writeln!(
+                text,
+                "  {}: {}",
+                seat.name.as_deref().unwrap_or("unnamed"),
+                capabilities.join(", ")
+            );
+        }
+
+        text.push_str("\nOutputs:\n");
+        for output in &self.outputs {
+            let mode = match (output.width, output.height) {
+                (Some(width), Some(height)) => format!("{width}x{height}"),
+                _ => "unknown mode".to_owned(),
+            };
+            let refresh = output
+                .refresh
+                .map(|refresh| format!("@{refresh:.3}Hz"))
+                .unwrap_or_default();
+            let _ = writeln!(
+                text,
+                "  {}: {mode}{refresh}, scale {}",
+                output.name.as_deref().unwrap_or("unnamed"),
+                output.scale
+            );
+        }
+
+        let _ = writeln!(
+            text,
+            "\nOverlay: {} with {} buffers",
+            self.shell.unwrap_or("unavailable"),
+            self.buffer_manager
+        );
+
+        match self.unavailable.is_empty() {
+            true => text.push_str("\nEvery feature is available.\n"),
+            false => {
+                text.push_str("\nUnavailable:\n");
+                for unavailable in &self.unavailable {
+                    let _ = writeln!(
+                        text,
+                        "  {} (needs {})",
+                        unavailable.feature, unavailable.needs
+                    );
+                }
+            }
+        }
+
+        text
+    }
+}
+
+/// Just enough state to hear about outputs, seats and shm formats
+struct DoctorState {
+    registry_state: RegistryState,
+    output_state: OutputState,
+    seat_state: SeatState,
+    shm: Option<Shm>,
+}
+
+impl OutputHandler for DoctorState {
+    fn output_state(&mut self) -> &mut OutputState {
+        &mut self.output_state
+    }
+
+    fn new_output(&mut self, _: &Connection, _: &QueueHandle<Self>, _: WlOutput) {}
+
+    fn update_output(&mut self, _: &Connection, _: &QueueHandle<Self>, _: WlOutput) {}
+
+    fn output_destroyed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: WlOutput) {}
+}
+
+impl SeatHandler for DoctorState {
+    fn seat_state(&mut self) -> &mut SeatState {
+        &mut self.seat_state
+    }
+
+    fn new_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: WlSeat) {}
+
+    fn new_capability(&mut self, _: &Connection, _: &QueueHandle<Self>, _: WlSeat, _: Capability) {}
+
+    fn remove_capability(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        _: WlSeat,
+        _: Capability,
+    ) {
+    }
+
+    fn remove_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: WlSeat) {}
+}
+
+impl ShmHandler for DoctorState {
+    fn shm_state(&mut self) -> &mut Shm {
+        self.shm.as_mut().expect("Shm events without shm")
+    }
+}
+
+impl ProvidesRegistryState for DoctorState {
+    fn registry(&mut self) -> &mut RegistryState {
+        &mut self.registry_state
+    }
+
+    registry_handlers![OutputState, SeatState];
+}
+
+delegate_output!(DoctorState);
+delegate_seat!(DoctorState);
+delegate_shm!(DoctorState);
+delegate_registry!(DoctorState);
@@ -0,0 +1,67 @@
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use crate::{dim::InputClass, DimOutcome, EventFormat};
+
+/// Things happening to a dim, printed to stdout as they happen when asked with `--events`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// We have connected to the display, dimming through the given backend
+    Connected {
+        backend: &'static str,
+    },
+    /// An output was announced, with its logical size if known
+    OutputAdded {
+        name: Option<String>,
+        width: Option<i32>,
+        height: Option<i32>,
+    },
+    OutputRemoved {
+        name: Option<String>,
+    },
+    FadeStarted {
+        direction: Fade,
+    },
+    FadeFinished {
+        direction: Fade,
+    },
+    /// Sent every [`EVENT_TICK_INTERVAL`](crate::consts::EVENT_TICK_INTERVAL) while dimming, with
+    /// the seconds left if the dim is not infinite
+    Tick {
+        remaining: Option<f32>,
+    },
+    /// Input which ended the dim, along with the name of the pressed key for keyboards
+    Input {
+        class: InputClass,
+        keysym: Option<String>,
+    },
+    Outcome {
+        outcome: DimOutcome,
+        code: u8,
+    },
+}
+
+/// Called with every event as it happens, e.g. given to [`Dimmer`](crate::Dimmer)
+pub type EventCallback = Box<dyn Fn(&Event)>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Fade {
+    In,
+    Out,
+}
+
+impl Event {
+    /// Print this event in the given format as a single line.
+    pub fn emit(&self, format: EventFormat) {
+        let line = match format {
+            EventFormat::Json => serde_json::to_string(self).expect("Events are always valid JSON"),
+        };
+
+        // a reader going away should not keep us from dimming
+        let mut stdout = io::stdout().lock();
+        let _ = writeln!(stdout, "{line}").and_then(|_| stdout.flush());
+    }
+}
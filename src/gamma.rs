@@ -0,0 +1,215 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, Seek, Write},
+    os::fd::{AsFd, FromRawFd},
+    sync::atomic::{AtomicBool, AtomicU32, Ordering},
+};
+
+use anyhow::{Context, Result};
+use log::warn;
+use smithay_client_toolkit::{
+    reexports::{
+        client::{
+            globals::GlobalList, protocol::wl_output::WlOutput, Connection, Dispatch, Proxy,
+            QueueHandle,
+        },
+        protocols_wlr::gamma_control::v1::client::{
+            zwlr_gamma_control_manager_v1::ZwlrGammaControlManagerV1,
+            zwlr_gamma_control_v1::{self, ZwlrGammaControlV1},
+        },
+    },
+    registry::SimpleGlobal,
+};
+
+use crate::{
+    backend::{Capabilities, DimBackend, Level, Output},
+    buffer::Color,
+    DimData,
+};
+
+/// Gamma of every output, scaled towards the color by the alpha. The compositor restores the
+/// original ramps of outputs we stop dimming.
+pub struct Gamma {
+    manager: SimpleGlobal<ZwlrGammaControlManagerV1, 1>,
+    controls: HashMap<WlOutput, GammaControl>,
+    qh: QueueHandle<DimData>,
+}
+
+impl Gamma {
+    pub fn bind(globals: &GlobalList, qh: &QueueHandle<DimData>) -> Result<Self> {
+        Ok(Self {
+            manager: SimpleGlobal::bind(globals, qh)?,
+            controls: HashMap::new(),
+            qh: qh.clone(),
+        })
+    }
+}
+
+impl DimBackend for Gamma {
+    fn name(&self) -> &str {
+        "gamma"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            color: true,
+            per_output: true,
+            input: false,
+        }
+    }
+
+    fn add_output(&mut self, output: &Output) {
+        let Ok(manager) = self.manager.get() else {
+            return;
+        };
+
+        let output = &output.wl_output;
+        self.controls
+            .entry(output.clone())
+            .or_insert_with(|| GammaControl::new(manager, output, &self.qh));
+    }
+
+    fn remove_output(&mut self, output: &WlOutput) {
+        self.controls.remove(output);
+    }
+
+    fn apply(&mut self, level: Level) {
+        // failed controls have been warned about as they failed
+        self.controls.retain(|_, control| !control.state().failed());
+        for control in self.controls.values_mut() {
+            control.set(level.alpha, level.color);
+        }
+    }
+
+    fn restore(&mut self) {
+        self.controls.clear();
+    }
+}
+
+/// Gamma of an output we dim by scaling its ramps, restored by the compositor once dropped
+struct GammaControl {
+    control: ZwlrGammaControlV1,
+    /// Alpha and color last set, as every step of the fade need not change them
+    shown: Option<(f32, Color)>,
+}
+
+impl GammaControl {
+    /// Take control of the gamma of the given output, which we may set once told its size.
+    fn new(
+        manager: &ZwlrGammaControlManagerV1,
+        output: &WlOutput,
+        qh: &QueueHandle<DimData>,
+    ) -> Self {
+        Self {
+            control: manager.get_gamma_control(output, qh, GammaState::default()),
+            shown: None,
+        }
+    }
+
+    fn state(&self) -> &GammaState {
+        self.control
+            .data::<GammaState>()
+            .expect("Gamma control without state")
+    }
+
+    /// Blend the output towards the given color like an overlay of the given alpha would.
+    fn set(&mut self, alpha: f32, color: Color) {
+        let Some(size) = self.state().size() else {
+            return;
+        };
+        if self.shown == Some((alpha, color)) {
+            return;
+        }
+
+        match write_ramps(size, alpha, color) {
+            Ok(file) => {
+                self.control.set_gamma(file.as_fd());
+                self.shown = Some((alpha, color));
+            }
+            Err(e) => warn!("Failed to set gamma: {e:#}"),
+        }
+    }
+}
+
+impl Drop for GammaControl {
+    fn drop(&mut self) {
+        // the compositor restores the original ramps
+        self.control.destroy();
+    }
+}
+
+/// What the compositor told us about a gamma control
+#[derive(Debug, Default)]
+struct GammaState {
+    /// Number of elements in each ramp, 0 until told
+    size: AtomicU32,
+    failed: AtomicBool,
+}
+
+impl GammaState {
+    fn size(&self) -> Option<usize> {
+        match self.size.load(Ordering::Relaxed) {
+            0 => None,
+            size => Some(size as usize),
+        }
+    }
+
+    fn failed(&self) -> bool {
+        self.failed.load(Ordering::Relaxed)
+    }
+}
+
+/// Write the red, green then blue ramps, each of the given size, to a file the compositor may
+/// read them from.
+fn write_ramps(size: usize, alpha: f32, color: Color) -> Result<File> {
+    // SAFETY: the name is a valid C string
+    let fd = unsafe { libc::memfd_create(c"dim-gamma".as_ptr(), libc::MFD_CLOEXEC) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error()).context("Failed to create memfd");
+    }
+    // SAFETY: the fd was just created, and nothing else owns it
+    let mut file = unsafe { File::from_raw_fd(fd) };
+
+    let alpha = alpha.clamp(0., 1.);
+    let last = size.saturating_sub(1).max(1) as f32;
+    let mut ramps = Vec::with_capacity(size * 3 * 2);
+    for tint in [color.r, color.g, color.b] {
+        let tint = f32::from(tint) / 255.;
+        for i in 0..size {
+            let value = (i as f32 / last) * (1. - alpha) + tint * alpha;
+            let value = (value * f32::from(u16::MAX)).round() as u16;
+            ramps.extend_from_slice(&value.to_ne_bytes());
+        }
+    }
+
+    file.write_all(&ramps)?;
+    // the compositor reads from the offset we share with it
+    file.rewind()?;
+    Ok(file)
+}
+
+impl Dispatch<ZwlrGammaControlV1, GammaState> for DimData {
+    fn event(
+        _: &mut Self,
+        control: &ZwlrGammaControlV1,
+        event: zwlr_gamma_control_v1::Event,
+        state: &GammaState,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_gamma_control_v1::Event::GammaSize { size } => {
+                state.size.store(size, Ordering::Relaxed);
+            }
+            zwlr_gamma_control_v1::Event::Failed => {
+                warn!(
+                    "Failed to control gamma ({}), is another program using it?",
+                    control.id()
+                );
+                state.failed.store(true, Ordering::Relaxed);
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use smithay_client_toolkit::reexports::{
+    client::{
+        protocol::{wl_pointer::WlPointer, wl_seat::WlSeat},
+        Connection, Dispatch, Proxy, QueueHandle,
+    },
+    protocols::wp::pointer_gestures::zv1::client::{
+        zwp_pointer_gesture_hold_v1::{self, ZwpPointerGestureHoldV1},
+        zwp_pointer_gesture_pinch_v1::{self, ZwpPointerGesturePinchV1},
+        zwp_pointer_gesture_swipe_v1::{self, ZwpPointerGestureSwipeV1},
+        zwp_pointer_gestures_v1::ZwpPointerGesturesV1,
+    },
+};
+
+use crate::{dim::InputClass, DimData};
+
+/// Touchpad gestures of a single pointer, which are not delivered as regular pointer events
+pub struct PointerGestures {
+    swipe: ZwpPointerGestureSwipeV1,
+    pinch: ZwpPointerGesturePinchV1,
+    /// Only available since version 3 of the protocol
+    hold: Option<ZwpPointerGestureHoldV1>,
+}
+
+impl PointerGestures {
+    pub fn new(
+        manager: &ZwpPointerGesturesV1,
+        pointer: &WlPointer,
+        seat: &WlSeat,
+        qh: &QueueHandle<DimData>,
+    ) -> Self {
+        Self {
+            swipe: manager.get_swipe_gesture(pointer, qh, seat.clone()),
+            pinch: manager.get_pinch_gesture(pointer, qh, seat.clone()),
+            hold: (manager.version() >= 3)
+                .then(|| manager.get_hold_gesture(pointer, qh, seat.clone())),
+        }
+    }
+}
+
+impl Drop for PointerGestures {
+    fn drop(&mut self) {
+        self.swipe.destroy();
+        self.pinch.destroy();
+        if let Some(hold) = &self.hold {
+            hold.destroy();
+        }
+    }
+}
+
+impl Dispatch<ZwpPointerGestureSwipeV1, WlSeat> for DimData {
+    fn event(
+        state: &mut Self,
+        _: &ZwpPointerGestureSwipeV1,
+        event: zwp_pointer_gesture_swipe_v1::Event,
+        seat: &WlSeat,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let zwp_pointer_gesture_swipe_v1::Event::Begin { .. } = event {
+            state.cancel(seat, InputClass::Gesture);
+        }
+    }
+}
+
+impl Dispatch<ZwpPointerGesturePinchV1, WlSeat> for DimData {
+    fn event(
+        state: &mut Self,
+        _: &ZwpPointerGesturePinchV1,
+        event: zwp_pointer_gesture_pinch_v1::Event,
+        seat: &WlSeat,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let zwp_pointer_gesture_pinch_v1::Event::Begin { .. } = event {
+            state.cancel(seat, InputClass::Gesture);
+        }
+    }
+}
+
+impl Dispatch<ZwpPointerGestureHoldV1, WlSeat> for DimData {
+    fn event(
+        state: &mut Self,
+        _: &ZwpPointerGestureHoldV1,
+        event: zwp_pointer_gesture_hold_v1::Event,
+        seat: &WlSeat,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let zwp_pointer_gesture_hold_v1::Event::Begin { .. } = event {
+            state.cancel(seat, InputClass::Gesture);
+        }
+    }
+}
@@ -0,0 +1,50 @@
+/// Match text against a glob, where `*` matches any run of characters and `?` any single one.
+pub fn matches(glob: &str, text: &str) -> bool {
+    matches_bytes(glob.as_bytes(), text.as_bytes())
+}
+
+fn matches_bytes(glob: &[u8], text: &[u8]) -> bool {
+    match (glob.split_first(), text.split_first()) {
+        (Some((b'*', rest)), _) => {
+            matches_bytes(rest, text) || (!text.is_empty() && matches_bytes(glob, &text[1..]))
+        }
+        (Some((b'?', rest)), Some((_, text_rest))) => matches_bytes(rest, text_rest),
+        (Some((g, rest)), Some((t, text_rest))) => g == t && matches_bytes(rest, text_rest),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::matches;
+
+    #[test]
+    fn literal() {
+        assert!(matches("spotify", "spotify"));
+        assert!(!matches("spotify", "spotify2"));
+        assert!(!matches("spotify", "spot"));
+        assert!(matches("", ""));
+        assert!(!matches("", "vlc"));
+    }
+
+    #[test]
+    fn star() {
+        assert!(matches("kdeconnect*", "kdeconnect"));
+        assert!(matches("kdeconnect*", "kdeconnect.phone"));
+        assert!(matches("*.instance*", "firefox.instance_1_42"));
+        assert!(matches("org.*.app", "org.jitsi.meet.app"));
+        assert!(matches("*", ""));
+        assert!(!matches("kdeconnect*", "chromium"));
+        assert!(!matches("*.app", "org.jitsi.meet"));
+    }
+
+    #[test]
+    fn question_mark() {
+        assert!(matches("mpv?", "mpv2"));
+        assert!(!matches("mpv?", "mpv"));
+        assert!(!matches("mpv?", "mpv12"));
+        assert!(matches("?*", "x"));
+        assert!(!matches("?*", ""));
+    }
+}
@@ -0,0 +1,139 @@
+use std::{
+    process::Child,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use log::{debug, info, warn};
+
+use crate::hooks;
+
+/// How often to check whether the locker has exited
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A locker we started once the dim completed, keeping the overlay up until it has locked the
+/// session so the desktop is never shown in between.
+pub struct Handoff {
+    child: Option<Child>,
+    command: String,
+    since: Instant,
+    delay: Duration,
+    locked: bool,
+}
+
+impl Handoff {
+    /// Run the given locker through `sh -c`, assuming it has locked the session once the delay
+    /// passes if we are not told so before.
+    pub fn spawn(command: &str, delay: Duration) -> Result<Self> {
+        debug!("Handing off to `{command}`");
+        Ok(Self {
+            child: Some(hooks::sh(command, &[])?),
+            command: command.to_owned(),
+            since: Instant::now(),
+            delay,
+            locked: false,
+        })
+    }
+
+    /// Our overlay has lost keyboard focus, taken by the session lock if the locker is still
+    /// running. Focus lost otherwise went elsewhere.
+    pub fn locked(&mut self) {
+        if !matches!(self.child.as_mut().map(Child::try_wait), Some(Ok(None))) {
+            debug!("Keyboard focus lost without `{}` running", self.command);
+            return;
+        }
+
+        debug!("Session locked by `{}`", self.command);
+        self.locked = true;
+    }
+
+    /// Whether our overlay may go, as the locker has locked the session, or has exited.
+    pub fn done(&mut self) -> bool {
+        if self.locked {
+            return true;
+        }
+
+        if self.since.elapsed() >= self.delay {
+            info!("Assuming `{}` has locked the session", self.command);
+            return true;
+        }
+
+        match self.child.as_mut().map(Child::try_wait) {
+            Some(Ok(Some(status))) => {
+                if !status.success() {
+                    warn!("`{}` exited with {status}", self.command);
+                }
+                self.child = None;
+                true
+            }
+            Some(Err(e)) => {
+                warn!("Failed to check on `{}`: {e}", self.command);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// How long until [`Handoff::done`] should be checked again
+    pub fn poll_timeout(&self) -> Duration {
+        self.delay
+            .saturating_sub(self.since.elapsed())
+            .min(POLL_INTERVAL)
+    }
+}
+
+impl Drop for Handoff {
+    /// The locker outlives the hand-off, and is waited on in the background.
+    fn drop(&mut self) {
+        if let Some(child) = self.child.take() {
+            hooks::detach(child, self.command.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use super::Handoff;
+
+    /// Long enough for the delay never to pass during a test
+    const NEVER: Duration = Duration::from_secs(60);
+
+    fn wait_done(handoff: &mut Handoff) {
+        for _ in 0..50 {
+            if handoff.done() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        panic!("Hand-off to `{}` never done", handoff.command);
+    }
+
+    #[test]
+    fn done_once_locked_while_running() {
+        let mut handoff = Handoff::spawn("sleep 5", NEVER).unwrap();
+        assert!(!handoff.done());
+
+        handoff.locked();
+        assert!(handoff.done());
+    }
+
+    #[test]
+    fn done_once_locker_exits() {
+        let mut handoff = Handoff::spawn("exit 1", NEVER).unwrap();
+        wait_done(&mut handoff);
+        assert!(handoff.child.is_none());
+
+        // focus lost after the locker exited is not the lock
+        handoff.locked();
+        assert!(!handoff.locked);
+    }
+
+    #[test]
+    fn done_once_delay_passes() {
+        let mut handoff = Handoff::spawn("sleep 5", Duration::ZERO).unwrap();
+        assert!(handoff.done());
+        assert_eq!(handoff.poll_timeout(), Duration::ZERO);
+    }
+}
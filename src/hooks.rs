@@ -0,0 +1,162 @@
+use std::{
+    process::{Child, Command},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+use log::{debug, warn};
+
+use crate::{signals, DimOutcome, HookMode};
+
+/// How often to check whether a waited hook has exited
+const WAIT_INTERVAL: Duration = Duration::from_millis(50);
+
+/// When hooks are run, each given as a list of commands to run through `sh -c`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hook {
+    /// A dim started showing
+    Start,
+    /// A dim completed without input, including as it turns outputs off
+    Timeout,
+    /// A dim ended any other way, see [`Report::cancel_reason`]
+    Cancel,
+    /// dim failed, with `DIM_ERROR` describing why
+    Error,
+}
+
+/// Commands to run as dims start and end
+#[derive(Debug, Default, Clone)]
+pub struct Hooks {
+    pub on_start: Vec<String>,
+    pub on_timeout: Vec<String>,
+    pub on_cancel: Vec<String>,
+    pub on_error: Vec<String>,
+    pub mode: HookMode,
+    /// How long a waited hook may run before being killed, if limited
+    pub timeout: Option<Duration>,
+}
+
+/// What hooks are told about a dim, through environment variables
+#[derive(Debug, Default, Clone)]
+pub struct Report {
+    pub outcome: Option<DimOutcome>,
+    /// How long the dim was showing for
+    pub elapsed: Duration,
+    /// What ended a dim which did not complete, e.g. `keyboard`, `request` or `inhibited`
+    pub cancel_reason: Option<&'static str>,
+    /// Names of the outputs dimmed
+    pub outputs: Vec<String>,
+}
+
+impl Report {
+    fn env(&self) -> Vec<(&'static str, String)> {
+        let mut env = vec![
+            ("DIM_ELAPSED", format!("{:.3}", self.elapsed.as_secs_f32())),
+            ("DIM_OUTPUTS", self.outputs.join(",")),
+        ];
+        if let Some(outcome) = self.outcome {
+            env.push(("DIM_OUTCOME", outcome.name().to_owned()));
+            env.push(("DIM_EXIT_CODE", outcome.exit_code().to_string()));
+        }
+        if let Some(reason) = self.cancel_reason {
+            env.push(("DIM_CANCEL_REASON", reason.to_owned()));
+        }
+
+        env
+    }
+}
+
+impl Hooks {
+    fn commands(&self, hook: Hook) -> &[String] {
+        match hook {
+            Hook::Start => &self.on_start,
+            Hook::Timeout => &self.on_timeout,
+            Hook::Cancel => &self.on_cancel,
+            Hook::Error => &self.on_error,
+        }
+    }
+
+    /// Run the commands of the start hook for the given dim.
+    pub fn started(&self, report: &Report) {
+        self.run(Hook::Start, &report.env());
+    }
+
+    /// Run the commands of the hook fitting the outcome of the given dim.
+    pub fn ended(&self, report: &Report) {
+        match report.outcome {
+            Some(DimOutcome::Completed | DimOutcome::PoweredOff) => {
+                self.run(Hook::Timeout, &report.env())
+            }
+            Some(_) => self.run(Hook::Cancel, &report.env()),
+            None => {}
+        }
+    }
+
+    /// Run the commands of the given hook in order, with the given environment variables set.
+    /// Failing commands are logged, not keeping the rest from running.
+    pub fn run(&self, hook: Hook, env: &[(&str, String)]) {
+        for command in self.commands(hook) {
+            debug!("Running {hook:?} hook `{command}`");
+            let result = match self.mode {
+                HookMode::Detached => spawn(command, env),
+                HookMode::Wait => wait(command, env, self.timeout),
+            };
+
+            if let Err(e) = result {
+                warn!("{hook:?} hook failed: {e:#}");
+            }
+        }
+    }
+}
+
+/// Start the given command through `sh -c` with the given environment variables set.
+pub fn sh(command: &str, env: &[(&str, String)]) -> Result<Child> {
+    signals::unblock_in_child(&mut Command::new("sh"))
+        .arg("-c")
+        .arg(command)
+        .envs(env.iter().map(|(key, value)| (key, value)))
+        .spawn()
+        .with_context(|| format!("Failed to run `{command}`"))
+}
+
+/// Run the given command through `sh -c` without blocking on it.
+pub fn spawn(command: &str, env: &[(&str, String)]) -> Result<()> {
+    detach(sh(command, env)?, command.to_owned());
+    Ok(())
+}
+
+/// Wait on the given child of the given command in the background, logging how it failed.
+pub fn detach(mut child: Child, command: String) {
+    thread::spawn(move || match child.wait() {
+        Ok(status) if !status.success() => warn!("`{command}` exited with {status}"),
+        Err(e) => warn!("Failed to wait on `{command}`: {e}"),
+        _ => {}
+    });
+}
+
+/// Run the given command through `sh -c` until it exits, killing it once the timeout passes.
+fn wait(command: &str, env: &[(&str, String)], timeout: Option<Duration>) -> Result<()> {
+    let mut child = sh(command, env)?;
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            child.kill()?;
+            child.wait()?;
+            bail!("`{command}` timed out");
+        }
+
+        thread::sleep(WAIT_INTERVAL);
+    };
+
+    if !status.success() {
+        bail!("`{command}` exited with {status}");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use smithay_client_toolkit::reexports::{
+    client::{protocol::wl_seat::WlSeat, Connection, Dispatch, Proxy, QueueHandle},
+    protocols::ext::idle_notify::v1::client::{
+        ext_idle_notification_v1::{self, ExtIdleNotificationV1},
+        ext_idle_notifier_v1::ExtIdleNotifierV1,
+    },
+};
+
+use crate::DimData;
+
+/// What we want the compositor to tell us about a seat's activity
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdleWatch {
+    /// Any user input after the given milliseconds without it, ignoring idle inhibitors
+    Input(u32),
+    /// The seat being idle for the given milliseconds, which idle inhibitors will prevent
+    Idle(u32),
+}
+
+/// Data of each idle notification, so we know what it is telling us about
+#[derive(Debug)]
+pub struct IdleNotificationData {
+    seat: WlSeat,
+    watch: IdleWatch,
+}
+
+impl IdleWatch {
+    pub fn notification(
+        self,
+        notifier: &ExtIdleNotifierV1,
+        seat: &WlSeat,
+        qh: &QueueHandle<DimData>,
+    ) -> ExtIdleNotificationV1 {
+        let data = IdleNotificationData {
+            seat: seat.clone(),
+            watch: self,
+        };
+
+        match self {
+            // input notifications are only available since version 2, before which inhibitors
+            // hide activity from us, see DimData::new
+            IdleWatch::Input(timeout) if notifier.version() >= 2 => {
+                notifier.get_input_idle_notification(timeout, seat, qh, data)
+            }
+            IdleWatch::Input(timeout) | IdleWatch::Idle(timeout) => {
+                notifier.get_idle_notification(timeout, seat, qh, data)
+            }
+        }
+    }
+}
+
+impl Dispatch<ExtIdleNotificationV1, IdleNotificationData> for DimData {
+    fn event(
+        state: &mut Self,
+        _: &ExtIdleNotificationV1,
+        event: ext_idle_notification_v1::Event,
+        data: &IdleNotificationData,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            ext_idle_notification_v1::Event::Idled => state.seat_idled(&data.seat, data.watch),
+            ext_idle_notification_v1::Event::Resumed => state.seat_resumed(&data.seat, data.watch),
+            _ => unreachable!("ext_idle_notification_v1 only has Idled and Resumed events"),
+        }
+    }
+}
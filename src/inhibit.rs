@@ -0,0 +1,111 @@
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::PathBuf,
+    process,
+};
+
+use log::debug;
+
+use crate::runtime;
+
+/// Records of `dim inhibit` holders are named with this, followed by the holder's PID. They hold
+/// the holder's start time on the first line, so we can tell when its PID was reused, then its
+/// command.
+const RECORD_PREFIX: &str = "inhibit-";
+
+/// A running `dim inhibit`, keeping us from dimming until it exits
+#[derive(Debug)]
+pub struct Holder {
+    pub pid: u32,
+    /// What the holder is running, as given to `dim inhibit`
+    pub command: String,
+}
+
+/// Our record as a holder, removed once dropped
+#[derive(Debug)]
+pub struct InhibitLock {
+    path: PathBuf,
+}
+
+impl InhibitLock {
+    /// Keep every instance of dim from dimming until this is dropped, noting the given command
+    /// as the reason.
+    pub fn hold(command: &str) -> io::Result<Self> {
+        let pid = process::id();
+        let start_time =
+            runtime::start_time(pid).ok_or_else(|| io::Error::other("No start time"))?;
+
+        // written aside then moved into place, so the record is never seen half written
+        let dir = runtime::create_dir()?;
+        let path = dir.join(format!("{RECORD_PREFIX}{pid}"));
+        let partial = dir.join(format!(".{RECORD_PREFIX}{pid}"));
+        writeln!(File::create(&partial)?, "{start_time}\n{command}")?;
+        fs::rename(&partial, &path)?;
+
+        Ok(Self { path })
+    }
+}
+
+impl Drop for InhibitLock {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            debug!("Failed to remove {:?}: {e}", self.path);
+        }
+    }
+}
+
+/// The live holders, removing the records of holders which died without cleaning up. Records we
+/// cannot make sense of are left alone.
+pub fn holders() -> io::Result<Vec<Holder>> {
+    let Some(dir) = runtime::dir().filter(|dir| dir.exists()) else {
+        return Ok(Vec::new());
+    };
+
+    let mut holders = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let Some(pid) = path
+            .file_name()
+            .and_then(|name| name.to_str()?.strip_prefix(RECORD_PREFIX)?.parse().ok())
+        else {
+            continue;
+        };
+
+        let record = match fs::read_to_string(&path) {
+            Ok(record) => record,
+            Err(e) => {
+                debug!("Failed to read inhibit record of {pid}: {e}");
+                continue;
+            }
+        };
+        let Some((started, command)) = record
+            .split_once('\n')
+            .and_then(|(started, command)| Some((started.parse::<u64>().ok()?, command)))
+        else {
+            debug!("Skipping unreadable inhibit record of {pid}");
+            continue;
+        };
+
+        if runtime::start_time(pid) == Some(started) {
+            let command = command.trim_end().to_owned();
+            holders.push(Holder { pid, command });
+        } else {
+            debug!("Removing stale inhibit record of {pid}");
+            let _ = fs::remove_file(&path);
+        }
+    }
+
+    Ok(holders)
+}
+
+/// Whether any `dim inhibit` is running
+pub fn held() -> bool {
+    match holders() {
+        Ok(holders) => !holders.is_empty(),
+        Err(e) => {
+            debug!("Failed to read inhibit records: {e}");
+            false
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use std::{
+    fs::{File, OpenOptions, TryLockError},
+    io::{Read, Seek, Write},
+    process, thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+use log::{debug, info, warn};
+
+use crate::{runtime, DimOutcome, InstancePolicy};
+
+/// How long a replaced dim may take to exit, e.g. fading out or running waited hooks
+const REPLACE_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often to check whether a replaced dim has exited
+const REPLACE_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Being the only dim dimming the current display, held until dropped
+#[derive(Debug)]
+pub struct InstanceLock {
+    /// Locked for as long as we run, holding our PID and then the exit code of our outcome
+    file: File,
+}
+
+/// What became of us after looking for another dim on the same display
+#[derive(Debug)]
+pub enum Instance {
+    /// We are the only one
+    Only(InstanceLock),
+    /// We waited for the other one, which exited with this code
+    Joined(u8),
+    /// We cannot tell, e.g. without a runtime directory
+    Unguarded,
+}
+
+impl InstanceLock {
+    /// Become the only dim dimming the current display, dealing with one already running
+    /// as the policy says.
+    pub fn acquire(policy: InstancePolicy) -> Result<Instance> {
+        let dir = match runtime::create_dir() {
+            Ok(dir) => dir,
+            Err(e) => {
+                warn!("Not guarding against other instances: {e}");
+                return Ok(Instance::Unguarded);
+            }
+        };
+        let path = dir.join(format!("instance-{}.lock", runtime::display()));
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .with_context(|| format!("Failed to open {path:?}"))?;
+
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let (pid, _) = read_record(&mut file);
+                let pid = pid.context("dim is already running on this display")?;
+
+                match policy {
+                    InstancePolicy::Refuse => {
+                        bail!("dim is already running on this display as {pid}, see --if-running.")
+                    }
+                    InstancePolicy::Replace => {
+                        info!("Replacing dim {pid}");
+                        // SAFETY: kill has no memory safety requirements
+                        if unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) } != 0 {
+                            debug!("Failed to signal {pid}, it may have already exited");
+                        }
+                        lock_within(&file, REPLACE_TIMEOUT).with_context(|| {
+                            format!("dim {pid} did not exit within {REPLACE_TIMEOUT:?}")
+                        })?;
+                    }
+                    InstancePolicy::Join => {
+                        info!("Waiting for the outcome of dim {pid}");
+                        // shared with other joiners, a replacer cannot take over the record
+                        // until we have read it
+                        file.lock_shared()?;
+                        let (holder, code) = read_record(&mut file);
+                        if holder != Some(pid) {
+                            bail!("dim {pid} was replaced before telling its outcome");
+                        }
+                        let code = code.context("The dim we joined exited without an outcome")?;
+                        return Ok(Instance::Joined(code));
+                    }
+                }
+            }
+            Err(TryLockError::Error(e)) => return Err(e.into()),
+        }
+
+        let mut lock = Self { file };
+        lock.write_record(None)?;
+        Ok(Instance::Only(lock))
+    }
+
+    /// Let any dims joining us know our outcome.
+    pub fn finish(&mut self, outcome: DimOutcome) -> Result<()> {
+        self.write_record(Some(outcome.exit_code()))
+    }
+
+    fn write_record(&mut self, code: Option<u8>) -> Result<()> {
+        self.file.set_len(0)?;
+        self.file.rewind()?;
+        writeln!(self.file, "{}", process::id())?;
+        if let Some(code) = code {
+            writeln!(self.file, "{code}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Lock the given file once its holder lets go, giving up after the timeout.
+fn lock_within(file: &File, timeout: Duration) -> Result<()> {
+    let since = Instant::now();
+    loop {
+        match file.try_lock() {
+            Ok(()) => return Ok(()),
+            Err(TryLockError::WouldBlock) if since.elapsed() < timeout => {
+                thread::sleep(REPLACE_INTERVAL)
+            }
+            Err(TryLockError::WouldBlock) => bail!("Timed out waiting for the lock"),
+            Err(TryLockError::Error(e)) => return Err(e.into()),
+        }
+    }
+}
+
+/// Read the PID and exit code written by the dim which holds, or held, the lock.
+fn read_record(file: &mut File) -> (Option<u32>, Option<u8>) {
+    let mut record = String::new();
+    if file
+        .rewind()
+        .and_then(|_| file.read_to_string(&mut record))
+        .is_err()
+    {
+        return (None, None);
+    }
+
+    let mut lines = record.lines();
+    let pid = lines.next().and_then(|line| line.parse().ok());
+    let code = lines.next().and_then(|line| line.parse().ok());
+    (pid, code)
+}
@@ -1,17 +1,73 @@
 mod dim;
+mod gamma;
+mod gestures;
+mod glob;
+mod handoff;
+mod idle;
+mod mpris;
 mod opts;
+mod outcome;
+mod overlay;
+mod power;
+mod runtime;
+mod screensaver;
+mod seat;
+mod state;
 mod surface;
+mod tablet;
+#[cfg(all(test, feature = "dbus"))]
+mod test_bus;
+mod timeline;
+mod toplevel;
 
+pub mod backend;
+pub mod backlight;
 pub mod buffer;
+pub mod control;
+pub mod dimmer;
+pub mod doctor;
+pub mod events;
+pub mod hooks;
+pub mod inhibit;
+pub mod instance;
+pub mod signals;
+#[cfg(feature = "x11")]
+pub mod x11;
 
-pub use dim::DimData;
-pub use opts::DimOpts;
+pub use dim::{DimData, InputClass};
+pub use dimmer::{Dimmer, DimmerBuilder};
+pub use opts::{
+    Backend, DaemonOpts, DimCommand, DimOpts, DoctorFormat, EventFormat, HookMode, InhibitPolicy,
+    InstancePolicy,
+};
+pub use outcome::DimOutcome;
+pub use overlay::Shell;
 pub use surface::DimSurface;
 
 pub mod consts {
+    use std::time::Duration;
+
     pub const DEFAULT_DURATION: u64 = 30;
     pub const DEFAULT_ALPHA: f32 = 0.5;
     pub const DEFAULT_FADE: f32 = 0.5;
+    pub const DEFAULT_IDLE: u64 = 270;
+    pub const DEFAULT_EXTEND_BY: u64 = 60;
+    pub const DEFAULT_THEN_DELAY: f32 = 2.;
+    pub const DEFAULT_BACKLIGHT_ROOT: &str = "/sys/class/backlight";
+
+    /// How long a seat must go without input before we start listening for its activity when
+    /// passing input through. Kept small so only a brief pause is needed between inputs.
+    pub const PASSTHROUGH_IDLE_TIMEOUT_MS: u32 = 1;
+    /// How long seats may be idle of input without being idle otherwise before we consider the
+    /// session inhibited.
+    pub const INHIBIT_GRACE: Duration = Duration::from_millis(250);
+    /// How often to check the inhibitors we keep track of ourselves, e.g. of playing media, while
+    /// waiting for them to clear or while dimming.
+    pub const INHIBITOR_POLL_INTERVAL: Duration = Duration::from_secs(2);
+    /// How often to tell how much time is left while dimming, when printing events.
+    pub const EVENT_TICK_INTERVAL: Duration = Duration::from_secs(1);
+    /// How often to step the backends which do not draw frames, e.g. the backlight, while fading.
+    pub const STEP_INTERVAL: Duration = Duration::from_millis(30);
 
     pub const CONFIG_FILENAME: &str = "config.toml";
 }
@@ -4,53 +4,183 @@ use std::{
     fs::File,
     io::read_to_string,
     path::{Path, PathBuf},
-    process, thread,
-    time::Duration,
+    process::{Command, ExitCode},
 };
 
-use anyhow::{anyhow, Context};
+use anyhow::{bail, Context};
 use clap::Parser;
-use dim_screen::{consts::CONFIG_FILENAME, DimData, DimOpts};
-use log::{debug, info};
-use smithay_client_toolkit::{
-    compositor::CompositorState,
-    reexports::client::{globals::registry_queue_init, Connection, EventQueue},
-    shell::wlr_layer::LayerShell,
+use dim_screen::{
+    consts::CONFIG_FILENAME,
+    control, doctor,
+    hooks::{self, Hook, Hooks},
+    inhibit::{self, InhibitLock},
+    instance::{Instance, InstanceLock},
+    DimCommand, DimOpts, DimOutcome, Dimmer,
 };
+use log::{debug, info, warn};
+use smithay_client_toolkit::reexports::client::Connection;
 
-fn main() -> anyhow::Result<()> {
+fn main() -> anyhow::Result<ExitCode> {
     env_logger::init();
     let args = DimOpts::parse();
     args.validate()?;
 
     if let Some(path) = args.gen_completions {
-        return DimOpts::generate_completions(&path);
+        DimOpts::generate_completions(&path)?;
+        return Ok(ExitCode::SUCCESS);
     }
 
-    let opts = match get_config(args.config.as_deref()).context("Failed to read config!")? {
-        Some(config) => config.merge_onto_self(args),
-        None => args,
-    };
+    let opts = get_config(args.config.as_deref())
+        .context("Failed to read config!")?
+        .unwrap_or_default()
+        .merge_onto_self(args);
 
     debug!("Using options: {opts:?}");
 
-    let duration = opts.duration();
-    // We consider a duration of 0 as infinite, not starting the timer
-    if duration > 0 {
-        thread::spawn(move || {
-            thread::sleep(Duration::from_secs(duration));
-            process::exit(0);
-        });
+    match &opts.command {
+        Some(DimCommand::Inhibit { list, command }) => return run_inhibit(*list, command),
+        Some(DimCommand::Doctor { format }) => {
+            doctor::diagnose()?.print(*format)?;
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(DimCommand::Ctl { request }) => {
+            println!("{}", control::send(&request.join(" "))?);
+            return Ok(ExitCode::SUCCESS);
+        }
+        _ => {}
+    }
+
+    // hooks are about dims, which other commands never show
+    let hooks = match opts.command {
+        None | Some(DimCommand::Daemon(_)) => opts.hooks(),
+        _ => Hooks::default(),
+    };
+
+    run(opts, &hooks).inspect_err(|e| hooks.run(Hook::Error, &[("DIM_ERROR", format!("{e:#}"))]))
+}
+
+/// Dim, or wait, as the given options tell us to.
+fn run(opts: DimOpts, hooks: &Hooks) -> anyhow::Result<ExitCode> {
+    let daemon_command = match opts.command {
+        Some(DimCommand::Daemon(_)) => Some(opts.daemon.command.clone()),
+        _ => None,
+    };
+    let needs_idle_notifier = opts.command.is_some();
+
+    // only one dim should be showing on a display at a time
+    let mut instance = match opts.command {
+        None | Some(DimCommand::Daemon(_)) => match InstanceLock::acquire(opts.if_running())? {
+            Instance::Only(lock) => Some(lock),
+            Instance::Joined(code) => return Ok(ExitCode::from(code)),
+            Instance::Unguarded => None,
+        },
+        _ => None,
+    };
+
+    let outcome = if uses_x11() {
+        run_x11(opts, hooks, instance.is_some())?
+    } else {
+        let mut dimmer = Dimmer::builder()
+            .opts(opts)
+            .hooks(hooks.clone())
+            // being the only dim on this display, we may take requests for it
+            .listen(instance.is_some())
+            .signals(true)
+            .build()?;
+        if needs_idle_notifier && !dimmer.data().has_idle_notifier() {
+            bail!("This command requires a compositor implementing the ext-idle-notify protocol.");
+        }
+
+        if let Some(command) = daemon_command {
+            return run_daemon(&mut dimmer, command.as_deref());
+        }
+
+        let outcome = dimmer.run()?;
+        // hooks have run as outputs went off, but we have to be around to turn them back on
+        if outcome == DimOutcome::PoweredOff {
+            dimmer.wait_powered_on()?;
+        }
+        outcome
+    };
+
+    if outcome != DimOutcome::Completed {
+        eprintln!("{outcome}");
+    }
+
+    if let Some(Err(e)) = instance.as_mut().map(|lock| lock.finish(outcome)) {
+        warn!("Failed to share outcome with joining instances: {e}");
+    }
+
+    Ok(outcome.into())
+}
+
+/// Whether to dim an X11 display, only if we are given one and cannot reach a Wayland display,
+/// e.g. as `WAYLAND_DISPLAY` is left over from another session.
+fn uses_x11() -> bool {
+    env::var_os("DISPLAY").is_some()
+        && Connection::connect_to_env()
+            .inspect_err(|e| debug!("Not dimming Wayland: {e}"))
+            .is_err()
+}
+
+/// Dim the X11 display until the dim ends, taking requests if we are the only dim on it.
+#[cfg(feature = "x11")]
+fn run_x11(opts: DimOpts, hooks: &Hooks, listen: bool) -> anyhow::Result<DimOutcome> {
+    if opts.command.is_some() {
+        bail!("Only dims are supported on X11, commands waiting on the session need Wayland.");
     }
 
-    let (mut data, mut event_queue) = create_wl_app(opts)?;
-    while !data.should_exit() {
-        event_queue
-            .blocking_dispatch(&mut data)
-            .context("Failed to block on events!")?;
+    dim_screen::x11::dim(opts, hooks, listen)
+}
+
+/// Stand-in for when we are built without X11 support
+#[cfg(not(feature = "x11"))]
+fn run_x11(_: DimOpts, _: &Hooks, _: bool) -> anyhow::Result<DimOutcome> {
+    bail!("No Wayland display is reachable, and dim was built without the x11 feature to dim X11.")
+}
+
+/// Dim every time the session goes idle, running the given command whenever a dim completes.
+fn run_daemon(dimmer: &mut Dimmer, command: Option<&str>) -> anyhow::Result<ExitCode> {
+    loop {
+        match dimmer.run()? {
+            outcome @ (DimOutcome::Completed | DimOutcome::PoweredOff) => {
+                info!("{outcome}");
+                if let Some(command) = command {
+                    hooks::spawn(command, &[])?;
+                }
+            }
+            DimOutcome::Interrupted => {
+                info!("{}", DimOutcome::Interrupted);
+                return Ok(DimOutcome::Interrupted.into());
+            }
+            outcome => info!("{outcome}"),
+        }
     }
+}
 
-    Err(anyhow!("Some user input was detected!"))
+/// Keep every dim from dimming while the given command runs, exiting as it did, or list who is
+/// doing so.
+fn run_inhibit(list: bool, command: &[String]) -> anyhow::Result<ExitCode> {
+    if list {
+        for holder in inhibit::holders().context("Failed to read inhibitors")? {
+            println!("{}\t{}", holder.pid, holder.command);
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let Some((program, args)) = command.split_first() else {
+        bail!("No command given to run.");
+    };
+
+    let _lock = InhibitLock::hold(&command.join(" ")).context("Failed to hold inhibitor")?;
+    let status = Command::new(program)
+        .args(args)
+        .status()
+        .with_context(|| format!("Failed to run `{program}`"))?;
+
+    Ok(status
+        .code()
+        .map_or(ExitCode::FAILURE, |code| ExitCode::from(code as u8)))
 }
 
 fn get_config(dir: Option<&Path>) -> anyhow::Result<Option<DimOpts>> {
@@ -86,19 +216,3 @@ fn get_config(dir: Option<&Path>) -> anyhow::Result<Option<DimOpts>> {
     config.validate()?;
     Ok(Some(config))
 }
-
-fn create_wl_app(opts: DimOpts) -> anyhow::Result<(DimData, EventQueue<DimData>)> {
-    let conn = Connection::connect_to_env().context("Failed to connect to environment")?;
-
-    let (globals, event_queue) =
-        registry_queue_init(&conn).context("Failed to initialize registry")?;
-    let qh = event_queue.handle();
-
-    let compositor = CompositorState::bind(&globals, &qh).context("Compositor not available")?;
-    let layer_shell = LayerShell::bind(&globals, &qh).context("Layer shell failed?")?;
-
-    Ok((
-        DimData::new(compositor, &globals, &qh, layer_shell, opts),
-        event_queue,
-    ))
-}
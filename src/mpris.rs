@@ -0,0 +1,169 @@
+#[cfg(feature = "dbus")]
+pub use imp::Mpris;
+
+#[cfg(feature = "dbus")]
+mod imp {
+    use std::time::Duration;
+
+    use anyhow::Result;
+    use log::debug;
+    use zbus::{
+        blocking::{connection::Builder, fdo::DBusProxy, Connection},
+        zvariant::OwnedValue,
+    };
+
+    use crate::glob;
+
+    /// Media players on the session bus which are playing, as told by the MPRIS interface. Many do
+    /// not set idle inhibitors, so we treat them as one ourselves.
+    pub struct Mpris {
+        conn: Connection,
+        /// Globs of player names to disregard, e.g. `kdeconnect*` for mirrored players
+        ignore_players: Vec<String>,
+    }
+
+    impl Mpris {
+        /// Bus names of players start with this, followed by the player's name
+        const PLAYER_PREFIX: &str = "org.mpris.MediaPlayer2.";
+        /// A player not answering in time is considered not playing
+        const TIMEOUT: Duration = Duration::from_secs(1);
+
+        /// Connect to the session bus, as given by `DBUS_SESSION_BUS_ADDRESS`.
+        pub fn connect(ignore_players: Vec<String>) -> Result<Self> {
+            Self::connect_with(Builder::session()?, ignore_players)
+        }
+
+        /// Connect to the bus the given builder is for, e.g. one of our own while testing.
+        fn connect_with(builder: Builder<'_>, ignore_players: Vec<String>) -> Result<Self> {
+            let conn = builder.method_timeout(Self::TIMEOUT).build()?;
+
+            Ok(Self {
+                conn,
+                ignore_players,
+            })
+        }
+
+        /// Names of the players which are currently playing, without their bus name prefix
+        pub fn playing(&self) -> Result<Vec<String>> {
+            let names = DBusProxy::new(&self.conn)?.list_names()?;
+
+            let mut playing = Vec::new();
+            for name in &names {
+                let Some(player) = name.strip_prefix(Self::PLAYER_PREFIX) else {
+                    continue;
+                };
+
+                if self.ignore_players.iter().any(|g| glob::matches(g, player)) {
+                    debug!("Ignoring player {player}");
+                    continue;
+                }
+
+                match self.playback_status(name) {
+                    Ok(status) if status == "Playing" => playing.push(player.to_owned()),
+                    Ok(_) => {}
+                    Err(e) => debug!("Failed to get playback status of {player}: {e}"),
+                }
+            }
+
+            Ok(playing)
+        }
+
+        fn playback_status(&self, name: &str) -> Result<String> {
+            let reply = self.conn.call_method(
+                Some(name),
+                "/org/mpris/MediaPlayer2",
+                Some("org.freedesktop.DBus.Properties"),
+                "Get",
+                &("org.mpris.MediaPlayer2.Player", "PlaybackStatus"),
+            )?;
+
+            let status: OwnedValue = reply.body().deserialize()?;
+            Ok(String::try_from(status)?)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use zbus::{blocking::Connection, interface};
+
+        use super::Mpris;
+        use crate::test_bus::TestBus;
+
+        /// A player which only tells its playback status
+        struct FakePlayer {
+            status: &'static str,
+        }
+
+        #[interface(name = "org.mpris.MediaPlayer2.Player")]
+        impl FakePlayer {
+            #[zbus(property)]
+            fn playback_status(&self) -> String {
+                self.status.to_owned()
+            }
+        }
+
+        fn fake_player(bus: &TestBus, name: &str, status: &'static str) -> Connection {
+            bus.builder()
+                .name(format!("{}{name}", Mpris::PLAYER_PREFIX))
+                .unwrap()
+                .serve_at("/org/mpris/MediaPlayer2", FakePlayer { status })
+                .unwrap()
+                .build()
+                .unwrap()
+        }
+
+        fn playing(bus: &TestBus, ignore_players: &[&str]) -> Vec<String> {
+            let ignore_players = ignore_players.iter().map(|&glob| glob.to_owned()).collect();
+            let mpris = Mpris::connect_with(bus.builder(), ignore_players).unwrap();
+
+            let mut playing = mpris.playing().unwrap();
+            playing.sort();
+            playing
+        }
+
+        #[test]
+        fn finds_playing_players() {
+            let bus = TestBus::start();
+            let _music = fake_player(&bus, "music", "Playing");
+            let _podcast = fake_player(&bus, "podcast", "Paused");
+            let _phone = fake_player(&bus, "kdeconnect.phone", "Playing");
+
+            assert_eq!(playing(&bus, &[]), ["kdeconnect.phone", "music"]);
+        }
+
+        #[test]
+        fn skips_ignored_players() {
+            let bus = TestBus::start();
+            let _music = fake_player(&bus, "music", "Playing");
+            let _phone = fake_player(&bus, "kdeconnect.phone", "Playing");
+
+            assert_eq!(playing(&bus, &["kdeconnect*"]), ["music"]);
+            assert!(playing(&bus, &["kdeconnect*", "mus?c"]).is_empty());
+        }
+
+        #[test]
+        fn nothing_plays_without_players() {
+            let bus = TestBus::start();
+            assert!(playing(&bus, &[]).is_empty());
+
+            let _podcast = fake_player(&bus, "podcast", "Paused");
+            let _video = fake_player(&bus, "video", "Stopped");
+            assert!(playing(&bus, &[]).is_empty());
+        }
+    }
+}
+
+/// Stand-in for when we are built without D-Bus support, which can never be connected.
+#[cfg(not(feature = "dbus"))]
+pub enum Mpris {}
+
+#[cfg(not(feature = "dbus"))]
+impl Mpris {
+    pub fn connect(_: Vec<String>) -> anyhow::Result<Self> {
+        Err(anyhow::anyhow!("dim was built without the dbus feature"))
+    }
+
+    pub fn playing(&self) -> anyhow::Result<Vec<String>> {
+        match *self {}
+    }
+}
@@ -1,55 +1,524 @@
-use std::path::{Path, PathBuf};
+#[cfg(feature = "cli")]
+use std::path::Path;
+use std::{mem, path::PathBuf, time::Duration};
 
 use anyhow::{anyhow, Result};
-use clap::{CommandFactory, Parser, ValueEnum};
+#[cfg(feature = "cli")]
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+#[cfg(feature = "cli")]
 use clap_complete::{generate_to, Shell};
 use serde::Deserialize;
 
-use crate::consts::{DEFAULT_ALPHA, DEFAULT_DURATION, DEFAULT_FADE};
+use crate::{
+    buffer::Color,
+    consts::{
+        DEFAULT_ALPHA, DEFAULT_BACKLIGHT_ROOT, DEFAULT_DURATION, DEFAULT_EXTEND_BY, DEFAULT_FADE,
+        DEFAULT_IDLE, DEFAULT_THEN_DELAY,
+    },
+    hooks::Hooks,
+};
 
-#[derive(Debug, Deserialize, Parser)]
-#[command(author, version, about)]
+#[derive(Debug, Default, Deserialize)]
+#[cfg_attr(feature = "cli", derive(Parser))]
+#[cfg_attr(feature = "cli", command(author, version, about))]
 pub struct DimOpts {
-    #[arg(
-        short,
-        long,
-        help = format!("Duration in seconds, 0 is infinite, [default: {DEFAULT_DURATION}]")
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            short,
+            long,
+            help = format!("Duration in seconds, 0 is infinite, [default: {DEFAULT_DURATION}]")
+        )
     )]
-    duration: Option<u64>,
+    pub(crate) duration: Option<u64>,
 
-    #[arg(
-        short,
-        long,
-        help = format!("0.0 is transparent, 1.0 is opaque. When opaque, cursor will be hidden. [default: {DEFAULT_ALPHA}]")
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            short,
+            long,
+            help = format!("0.0 is transparent, 1.0 is opaque. When opaque, cursor will be hidden. [default: {DEFAULT_ALPHA}]")
+        )
     )]
-    alpha: Option<f32>,
+    pub(crate) alpha: Option<f32>,
 
-    #[arg(
-        short,
-        long,
-        help = format!("Fade-in animation duration in seconds. [default: {DEFAULT_FADE}]")
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            short,
+            long,
+            help = format!("Fade-in animation duration in seconds. [default: {DEFAULT_FADE}]")
+        )
     )]
     #[serde(default)]
     pub fade: Option<f32>,
 
-    #[arg(
-        short,
-        long,
-        help = "Make dim ignore input, passing it to lower surfaces. (You probably want to use `-d 0` with this)"
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long,
+            value_name = "SECONDS",
+            help = "Fade-out animation duration in seconds when stopped by SIGTERM or SIGINT. [default: 0]"
+        )
+    )]
+    #[serde(default)]
+    pub fade_out: Option<f32>,
+
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long,
+            value_name = "SECONDS",
+            help = format!("Seconds SIGUSR1 adds to the duration of a dim. [default: {DEFAULT_EXTEND_BY}]")
+        )
+    )]
+    #[serde(default)]
+    extend_by: Option<u64>,
+
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long,
+            value_name = "COLOR",
+            help = "Color of the overlay as #RRGGBB. [default: #000000]"
+        )
+    )]
+    #[serde(default)]
+    pub color: Option<Color>,
+
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long = "backend",
+            value_enum,
+            value_name = "BACKEND",
+            help = "How to dim, may be repeated to dim in several ways at once. [default: overlay]"
+        )
+    )]
+    #[serde(default)]
+    pub backends: Vec<Backend>,
+
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long,
+            value_name = "PATH",
+            help = format!("Where to find backlight devices for the backlight backend. [default: {DEFAULT_BACKLIGHT_ROOT}]")
+        )
+    )]
+    #[serde(default)]
+    backlight_root: Option<PathBuf>,
+
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            short,
+            long,
+            help = "Make dim ignore input, passing it to lower surfaces. (You probably want to use `-d 0` with this)"
+        )
     )]
     #[serde(default)]
     pub passthrough: bool,
 
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long,
+            value_enum,
+            help = "What to do while an idle inhibitor is active, e.g. of a playing video. [default: exit]"
+        )
+    )]
+    #[serde(default)]
+    pub(crate) on_inhibit: Option<InhibitPolicy>,
+
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long = "seat",
+            value_name = "NAME",
+            help = "Only let input from the given seat cancel dim, may be repeated. [default: all seats]"
+        )
+    )]
+    #[serde(default)]
+    pub seats: Vec<String>,
+
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long,
+            help = "Treat playing MPRIS media players as idle inhibitors, see --on-inhibit. Requires the dbus feature"
+        )
+    )]
+    #[serde(default)]
+    pub mpris: bool,
+
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long = "ignore-player",
+            value_name = "NAME",
+            help = "Don't consider MPRIS players with a matching name, may be repeated. Accepts `*` and `?` wildcards"
+        )
+    )]
+    #[serde(default)]
+    pub ignore_players: Vec<String>,
+
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long,
+            help = "Don't dim while a fullscreen window is focused, e.g. of a game"
+        )
+    )]
+    #[serde(default)]
+    pub skip_if_fullscreen: bool,
+
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long = "exempt",
+            value_name = "APP_ID",
+            help = "Don't dim while a window with a matching app ID is focused, may be repeated. Accepts `*` and `?` wildcards"
+        )
+    )]
+    #[serde(default)]
+    pub exempt_app_ids: Vec<String>,
+
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long,
+            value_enum,
+            value_name = "POLICY",
+            help = "What to do if dim is already running on this display. [default: refuse]"
+        )
+    )]
+    #[serde(default)]
+    if_running: Option<InstancePolicy>,
+
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long,
+            value_enum,
+            value_name = "FORMAT",
+            help = "Print an event per line on stdout as things happen, e.g. for status bars"
+        )
+    )]
+    #[serde(default)]
+    pub events: Option<EventFormat>,
+
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long,
+            value_name = "COMMAND",
+            help = "Locker to run through `sh -c` once the dim completes, keeping the overlay up until it has locked the session"
+        )
+    )]
+    #[serde(default)]
+    pub then: Option<String>,
+
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long,
+            value_name = "SECONDS",
+            help = format!("Seconds after which the locker given to --then is assumed to have locked the session. [default: {DEFAULT_THEN_DELAY}]")
+        )
+    )]
+    #[serde(default)]
+    then_delay: Option<f32>,
+
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long,
+            help = "Fade the overlay to opaque while handing off to the locker given to --then"
+        )
+    )]
+    #[serde(default)]
+    pub then_opaque: bool,
+
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long,
+            help = "Turn the dimmed outputs off once the dim completes, back on once the user returns"
+        )
+    )]
+    #[serde(default)]
+    pub power_off_on_timeout: bool,
+
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long = "on-start",
+            value_name = "COMMAND",
+            help = "Command to run through `sh -c` whenever a dim starts, may be repeated"
+        )
+    )]
+    #[serde(default)]
+    pub on_start: Vec<String>,
+
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long = "on-timeout",
+            value_name = "COMMAND",
+            help = "Command to run through `sh -c` whenever a dim completes, may be repeated"
+        )
+    )]
+    #[serde(default)]
+    pub on_timeout: Vec<String>,
+
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long = "on-cancel",
+            value_name = "COMMAND",
+            help = "Command to run through `sh -c` whenever a dim ends otherwise, may be repeated"
+        )
+    )]
+    #[serde(default)]
+    pub on_cancel: Vec<String>,
+
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long = "on-error",
+            value_name = "COMMAND",
+            help = "Command to run through `sh -c` if dim fails, may be repeated"
+        )
+    )]
+    #[serde(default)]
+    pub on_error: Vec<String>,
+
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long,
+            value_enum,
+            value_name = "MODE",
+            help = "Whether to wait for hook commands to exit before going on. [default: detached]"
+        )
+    )]
+    #[serde(default)]
+    hook_mode: Option<HookMode>,
+
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long,
+            value_name = "SECONDS",
+            help = "Seconds a waited hook command may run before being killed, 0 is unlimited. [default: 0]"
+        )
+    )]
+    #[serde(default)]
+    hook_timeout: Option<u64>,
+
+    /// Options for `dim daemon`, read from the `[daemon]` table of the config.
+    #[cfg_attr(feature = "cli", arg(skip))]
+    #[serde(default)]
+    pub daemon: DaemonOpts,
+
     #[serde(skip)]
-    #[arg(long, value_name = "PATH", help = "Generate completions at given path")]
+    #[cfg_attr(feature = "cli", command(subcommand))]
+    pub command: Option<DimCommand>,
+
+    #[serde(skip)]
+    #[cfg_attr(
+        feature = "cli",
+        arg(long, value_name = "PATH", help = "Generate completions at given path")
+    )]
     pub gen_completions: Option<PathBuf>,
 
     #[serde(skip)]
-    #[arg(short, long, value_name = "PATH", help = "Use config at path")]
+    #[cfg_attr(
+        feature = "cli",
+        arg(short, long, value_name = "PATH", help = "Use config at path")
+    )]
     pub config: Option<PathBuf>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// A translucent surface over every output
+    Overlay,
+    /// Lower the brightness of backlight devices, e.g. of laptop panels
+    Backlight,
+    /// Scale the gamma ramps of every output
+    Gamma,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum InhibitPolicy {
+    /// Exit without dimming, or stop dimming once one appears
+    #[default]
+    Exit,
+    /// Wait for inhibitors to clear before dimming, stopping if one appears
+    Wait,
+    /// Dim regardless of inhibitors
+    Ignore,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum InstancePolicy {
+    /// Exit with an error
+    #[default]
+    Refuse,
+    /// Make the running one exit, then run in its place
+    Replace,
+    /// Wait for the running one to finish, exiting as it did
+    Join,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum HookMode {
+    /// Run hooks in the background
+    #[default]
+    Detached,
+    /// Wait for each hook to exit, holding the dim meanwhile
+    Wait,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum EventFormat {
+    /// A JSON object per line, its `event` field telling which event it is
+    Json,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum DoctorFormat {
+    /// Sections meant to be read
+    #[default]
+    Text,
+    /// A single JSON object
+    Json,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "cli", derive(Subcommand))]
+pub enum DimCommand {
+    /// Keep running, dimming whenever the session has been idle for a while.
+    Daemon(DaemonOpts),
+    /// Block until the session has been idle for the given seconds, without dimming.
+    WaitIdle {
+        #[cfg_attr(feature = "cli", arg(help = "Seconds the session must be idle for"))]
+        seconds: u64,
+    },
+    /// Block until any user activity, without dimming.
+    WaitActive,
+    /// Send a request to the dim running on this display, e.g. `status`, `cancel`, `finish`,
+    /// `extend 30` or `set alpha=0.8`.
+    Ctl {
+        #[cfg_attr(feature = "cli", arg(required = true, value_name = "REQUEST"))]
+        request: Vec<String>,
+    },
+    /// Keep every dim from dimming while the given command runs.
+    Inhibit {
+        #[cfg_attr(
+            feature = "cli",
+            arg(
+                short,
+                long,
+                conflicts_with = "command",
+                help = "List running inhibitors instead"
+            )
+        )]
+        list: bool,
+
+        #[cfg_attr(
+            feature = "cli",
+            arg(
+                last = true,
+                required_unless_present = "list",
+                value_name = "COMMAND",
+                help = "Command to run, after `--`"
+            )
+        )]
+        command: Vec<String>,
+    },
+    /// Tell what the compositor offers dim, and which features it does not allow for.
+    Doctor {
+        #[cfg_attr(
+            feature = "cli",
+            arg(
+                long,
+                value_enum,
+                default_value_t = DoctorFormat::Text,
+                help = "How to print the diagnosis"
+            )
+        )]
+        format: DoctorFormat,
+    },
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[cfg_attr(feature = "cli", derive(Args))]
+pub struct DaemonOpts {
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            short,
+            long,
+            help = format!("Seconds idle before dimming, [default: {DEFAULT_IDLE}]")
+        )
+    )]
+    idle: Option<u64>,
+
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            short = 'x',
+            long,
+            value_name = "COMMAND",
+            help = "Command to run through `sh -c` whenever a dim completes, e.g. your locker"
+        )
+    )]
+    pub command: Option<String>,
+
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long,
+            help = "Serve org.freedesktop.ScreenSaver on the session bus, not dimming while apps inhibit it. Requires the dbus feature"
+        )
+    )]
+    #[serde(default)]
+    pub screensaver: bool,
+}
+
+impl DaemonOpts {
+    /// Merge other onto self, with other's values taking precedent
+    pub fn merge_onto_self(self, other: DaemonOpts) -> Self {
+        Self {
+            idle: other.idle.or(self.idle),
+            command: other.command.or(self.command),
+            screensaver: self.screensaver || other.screensaver,
+        }
+    }
+
+    /// Get user desired idle time or the default value.
+    pub fn idle(&self) -> u64 {
+        self.idle.unwrap_or(DEFAULT_IDLE)
+    }
+}
+
 impl DimOpts {
+    #[cfg(feature = "cli")]
     pub fn generate_completions(dir: &Path) -> anyhow::Result<()> {
         let mut cli = Self::command();
 
@@ -61,12 +530,76 @@ impl DimOpts {
         Ok(())
     }
 
-    /// Merge other onto self, with other's values taking precedent
-    pub fn merge_onto_self(self, other: DimOpts) -> Self {
+    /// Merge other onto self, with other's values taking precedent. Options given to a subcommand
+    /// are moved onto their config table, e.g. `dim daemon` arguments end up in `daemon`.
+    pub fn merge_onto_self(self, mut other: DimOpts) -> Self {
+        let daemon = match other.command.as_mut() {
+            Some(DimCommand::Daemon(daemon)) => self.daemon.merge_onto_self(mem::take(daemon)),
+            _ => self.daemon,
+        };
+
         Self {
             duration: other.duration.or(self.duration),
             alpha: other.alpha.or(self.alpha),
+            fade: other.fade.or(self.fade),
+            fade_out: other.fade_out.or(self.fade_out),
+            extend_by: other.extend_by.or(self.extend_by),
+            color: other.color.or(self.color),
+            on_inhibit: other.on_inhibit.or(self.on_inhibit),
+            if_running: other.if_running.or(self.if_running),
+            events: other.events.or(self.events),
+            backends: if other.backends.is_empty() {
+                self.backends
+            } else {
+                other.backends
+            },
+            backlight_root: other.backlight_root.or(self.backlight_root),
+            then: other.then.or(self.then),
+            then_delay: other.then_delay.or(self.then_delay),
+            then_opaque: self.then_opaque || other.then_opaque,
+            power_off_on_timeout: self.power_off_on_timeout || other.power_off_on_timeout,
+            hook_mode: other.hook_mode.or(self.hook_mode),
+            hook_timeout: other.hook_timeout.or(self.hook_timeout),
+            on_start: if other.on_start.is_empty() {
+                self.on_start
+            } else {
+                other.on_start
+            },
+            on_timeout: if other.on_timeout.is_empty() {
+                self.on_timeout
+            } else {
+                other.on_timeout
+            },
+            on_cancel: if other.on_cancel.is_empty() {
+                self.on_cancel
+            } else {
+                other.on_cancel
+            },
+            on_error: if other.on_error.is_empty() {
+                self.on_error
+            } else {
+                other.on_error
+            },
             passthrough: self.passthrough || other.passthrough,
+            seats: if other.seats.is_empty() {
+                self.seats
+            } else {
+                other.seats
+            },
+            mpris: self.mpris || other.mpris,
+            ignore_players: if other.ignore_players.is_empty() {
+                self.ignore_players
+            } else {
+                other.ignore_players
+            },
+            skip_if_fullscreen: self.skip_if_fullscreen || other.skip_if_fullscreen,
+            exempt_app_ids: if other.exempt_app_ids.is_empty() {
+                self.exempt_app_ids
+            } else {
+                other.exempt_app_ids
+            },
+            daemon,
+            command: other.command,
 
             ..self
         }
@@ -91,6 +624,14 @@ impl DimOpts {
             }
         }
 
+        if self.fade_out.is_some_and(|fade_out| fade_out < 0.) {
+            return Err(anyhow!("Fade-out must be at least 0."));
+        }
+
+        if self.then_delay.is_some_and(|delay| delay < 0.) {
+            return Err(anyhow!("Then delay must be at least 0."));
+        }
+
         Ok(())
     }
 
@@ -108,4 +649,65 @@ impl DimOpts {
     pub fn fade(&self) -> f32 {
         self.fade.unwrap_or(DEFAULT_FADE)
     }
+
+    /// Get user desired fade-out or no fade-out.
+    pub fn fade_out(&self) -> f32 {
+        self.fade_out.unwrap_or_default()
+    }
+
+    /// Get user desired extension per SIGUSR1 or the default value.
+    pub fn extend_by(&self) -> u64 {
+        self.extend_by.unwrap_or(DEFAULT_EXTEND_BY)
+    }
+
+    /// Get user desired delay before assuming the locker has locked or the default value.
+    pub fn then_delay(&self) -> f32 {
+        self.then_delay.unwrap_or(DEFAULT_THEN_DELAY)
+    }
+
+    /// Get user desired backends or the default of only the overlay.
+    pub fn backends(&self) -> Vec<Backend> {
+        match self.backends.is_empty() {
+            true => vec![Backend::Overlay],
+            false => self.backends.clone(),
+        }
+    }
+
+    /// Get user desired backlight root or the default value.
+    pub fn backlight_root(&self) -> PathBuf {
+        self.backlight_root
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_BACKLIGHT_ROOT))
+    }
+
+    /// Get the hooks to run as dims start and end.
+    pub fn hooks(&self) -> Hooks {
+        Hooks {
+            on_start: self.on_start.clone(),
+            on_timeout: self.on_timeout.clone(),
+            on_cancel: self.on_cancel.clone(),
+            on_error: self.on_error.clone(),
+            mode: self.hook_mode.unwrap_or_default(),
+            timeout: self
+                .hook_timeout
+                .filter(|&secs| secs > 0)
+                .map(Duration::from_secs),
+        }
+    }
+
+    /// Get user desired inhibitor policy or the default value.
+    pub fn on_inhibit(&self) -> InhibitPolicy {
+        self.on_inhibit.unwrap_or_default()
+    }
+
+    /// Whether to ask the compositor about idle inhibitors before dimming, waiting to hear back
+    /// from it.
+    pub fn checks_idle_inhibitors(&self) -> bool {
+        self.on_inhibit() != InhibitPolicy::Ignore
+    }
+
+    /// Get user desired policy towards other instances or the default value.
+    pub fn if_running(&self) -> InstancePolicy {
+        self.if_running.unwrap_or_default()
+    }
 }
@@ -0,0 +1,68 @@
+use std::{fmt, process::ExitCode};
+
+use serde::Serialize;
+
+/// How a dim ended, each with its own exit code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DimOutcome {
+    /// The duration passed without any user input
+    Completed,
+    /// Some user input was detected before the duration passed
+    Cancelled,
+    /// An idle inhibitor was active, e.g. of a playing video
+    Inhibited,
+    /// A fullscreen or exempted app was focused
+    Exempted,
+    /// We were told to stop by SIGTERM or SIGINT
+    Interrupted,
+    /// The duration passed without any user input, after which outputs were turned off until the
+    /// user returns
+    PoweredOff,
+}
+
+impl DimOutcome {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            DimOutcome::Completed => 0,
+            DimOutcome::Cancelled => 1,
+            DimOutcome::Inhibited => 2,
+            DimOutcome::Exempted => 3,
+            DimOutcome::Interrupted => 4,
+            DimOutcome::PoweredOff => 5,
+        }
+    }
+
+    /// Name of this outcome, as serialized
+    pub fn name(&self) -> &'static str {
+        match self {
+            DimOutcome::Completed => "completed",
+            DimOutcome::Cancelled => "cancelled",
+            DimOutcome::Inhibited => "inhibited",
+            DimOutcome::Exempted => "exempted",
+            DimOutcome::Interrupted => "interrupted",
+            DimOutcome::PoweredOff => "poweredoff",
+        }
+    }
+}
+
+impl From<DimOutcome> for ExitCode {
+    fn from(outcome: DimOutcome) -> Self {
+        ExitCode::from(outcome.exit_code())
+    }
+}
+
+impl fmt::Display for DimOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DimOutcome::Completed => write!(f, "No user input was detected."),
+            DimOutcome::Cancelled => write!(f, "Some user input was detected!"),
+            DimOutcome::Inhibited => write!(f, "An idle inhibitor is active!"),
+            DimOutcome::Exempted => write!(f, "A fullscreen or exempted app is focused!"),
+            DimOutcome::Interrupted => write!(f, "Interrupted by a signal!"),
+            DimOutcome::PoweredOff => {
+                write!(f, "No user input was detected, outputs were turned off.")
+            }
+        }
+    }
+}
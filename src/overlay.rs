@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+
+use log::{debug, warn};
+use smithay_client_toolkit::{
+    compositor::{CompositorState, Region},
+    reexports::{
+        client::{
+            globals::GlobalList,
+            protocol::{wl_output::WlOutput, wl_surface::WlSurface},
+            QueueHandle,
+        },
+        protocols::wp::{
+            single_pixel_buffer::v1::client::wp_single_pixel_buffer_manager_v1::WpSinglePixelBufferManagerV1,
+            viewporter::client::wp_viewporter::WpViewporter,
+        },
+    },
+    registry::SimpleGlobal,
+    shell::{
+        wlr_layer::{KeyboardInteractivity, Layer, LayerShell},
+        xdg::{window::WindowDecorations, XdgShell},
+        WaylandSurface,
+    },
+    shm::{slot::SlotPool, Shm},
+};
+
+use crate::{
+    backend::{Capabilities, DimBackend, Level, Output},
+    buffer::BufferManager,
+    surface::ShellSurface,
+    DimData, DimSurface,
+};
+
+/// How we show surfaces over every output
+pub enum Shell {
+    Layer(LayerShell),
+    /// Fallback for compositors without the layer shell, showing fullscreen windows which may
+    /// not cover everything, e.g. panels
+    Xdg(XdgShell),
+}
+
+impl Shell {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Shell::Layer(_) => "layer-shell",
+            Shell::Xdg(_) => "xdg-shell",
+        }
+    }
+}
+
+/// Translucent surfaces over every output, drawn at the pace of their frames
+pub struct Overlay {
+    compositor: CompositorState,
+    shell: Shell,
+    buffer_mgr: BufferManager,
+    viewporter: SimpleGlobal<WpViewporter, 1>,
+    passthrough: bool,
+    surfaces: HashMap<WlOutput, DimSurface>,
+    /// Latest level we were asked to show
+    level: Option<Level>,
+    qh: QueueHandle<DimData>,
+}
+
+impl Overlay {
+    pub fn new(
+        compositor: CompositorState,
+        shell: Shell,
+        globals: &GlobalList,
+        qh: &QueueHandle<DimData>,
+        passthrough: bool,
+    ) -> Self {
+        let buffer_mgr = match SimpleGlobal::<WpSinglePixelBufferManagerV1, 1>::bind(globals, qh) {
+            Ok(sg) => BufferManager::SinglePixel(sg),
+            Err(_) => {
+                warn!("Single pixel buffer not available! Using fallback.");
+
+                let shm = Shm::bind(globals, qh).expect("Could not create shm.");
+                let pool = SlotPool::new(1, &shm).expect("Failed to create pool!");
+                BufferManager::Shm(shm, pool)
+            }
+        };
+
+        Self {
+            compositor,
+            shell,
+            buffer_mgr,
+            viewporter: SimpleGlobal::<WpViewporter, 1>::bind(globals, qh)
+                .expect("wp_viewporter not available"),
+            passthrough,
+            surfaces: HashMap::new(),
+            level: None,
+            qh: qh.clone(),
+        }
+    }
+
+    pub fn buffer_mgr_mut(&mut self) -> &mut BufferManager {
+        &mut self.buffer_mgr
+    }
+
+    /// The compositor configured one of our surfaces, to the given size if it chose one, which
+    /// we may now draw.
+    pub fn configure(&mut self, surface: &WlSurface, size: Option<(u32, u32)>) {
+        let Some(view) = self
+            .surfaces
+            .values_mut()
+            .find(|view| view.wl_surface() == surface)
+        else {
+            debug!("Configure received for a surface we have since dropped.");
+            return;
+        };
+
+        view.configure(size.map(|(width, height)| (width as _, height as _)));
+        if let Some(level) = self.level {
+            view.show(&self.qh, &mut self.buffer_mgr, level.alpha, level.color);
+        }
+    }
+
+    /// One of our surfaces may be drawn again, showing the given level.
+    pub fn frame(&mut self, surface: &WlSurface, level: Level) {
+        self.level = Some(level);
+        let Some(view) = self
+            .surfaces
+            .values_mut()
+            .find(|view| view.wl_surface() == surface)
+        else {
+            debug!("Frame received for a surface we have since dropped.");
+            return;
+        };
+
+        view.frame_done();
+        view.show(&self.qh, &mut self.buffer_mgr, level.alpha, level.color);
+    }
+
+    /// Create a new dimmed surface to show on the given output
+    fn new_surface(&mut self, output: &Output) -> DimSurface {
+        let qh = &self.qh;
+        let surface = self.compositor.create_surface(qh);
+
+        let (width, height) = output
+            .info
+            .as_ref()
+            .and_then(|info| info.logical_size)
+            .unwrap_or((1920, 1080)); // no info for this output yet, default size
+
+        if self.passthrough {
+            let input_region = Region::new(&self.compositor).expect("Failed to get a wl_region");
+            surface.set_input_region(Some(input_region.wl_region()));
+        }
+
+        let surface = match &self.shell {
+            Shell::Layer(layer_shell) => {
+                let layer = layer_shell.create_layer_surface(
+                    qh,
+                    surface,
+                    Layer::Overlay,
+                    Some("dim_layer"),
+                    Some(&output.wl_output),
+                );
+
+                layer.set_keyboard_interactivity(match self.passthrough {
+                    true => KeyboardInteractivity::None,
+                    false => KeyboardInteractivity::Exclusive,
+                });
+                layer.set_exclusive_zone(-1);
+                layer.set_size(width as _, height as _);
+                layer.commit();
+                ShellSurface::Layer(layer)
+            }
+            Shell::Xdg(xdg_shell) => {
+                // we draw no decorations of our own, leaving the window borderless
+                let window = xdg_shell.create_window(surface, WindowDecorations::ClientOnly, qh);
+                window.set_title("dim");
+                window.set_app_id("dim");
+                window.set_fullscreen(Some(&output.wl_output));
+                window.commit();
+                ShellSurface::Xdg(window)
+            }
+        };
+
+        let viewport = self
+            .viewporter
+            .get()
+            .expect("wp_viewporter failed")
+            .get_viewport(surface.wl_surface(), qh, ());
+
+        let color = self.level.map(|level| level.color).unwrap_or_default();
+        let buffer = self.buffer_mgr.get_buffer(qh, color, 0.);
+        let back_buffer = self.buffer_mgr.get_buffer(qh, color, 0.);
+        let mut view = DimSurface::new(buffer, back_buffer, viewport, surface);
+        // windows may be configured without a size, leaving it to us
+        view.set_size(width, height);
+        view
+    }
+}
+
+impl DimBackend for Overlay {
+    fn name(&self) -> &str {
+        "overlay"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            color: true,
+            per_output: true,
+            input: !self.passthrough,
+        }
+    }
+
+    fn add_output(&mut self, output: &Output) {
+        let view = self.new_surface(output);
+        self.surfaces.insert(output.wl_output.clone(), view);
+    }
+
+    fn remove_output(&mut self, output: &WlOutput) {
+        self.surfaces.remove(output);
+    }
+
+    /// Show the given level on every surface not waiting for a frame, the others showing the
+    /// level of the time of their frame.
+    fn apply(&mut self, level: Level) {
+        self.level = Some(level);
+        for view in self.surfaces.values_mut() {
+            view.show(&self.qh, &mut self.buffer_mgr, level.alpha, level.color);
+        }
+    }
+
+    fn restore(&mut self) {
+        self.surfaces.clear();
+        self.level = None;
+    }
+}
@@ -0,0 +1,66 @@
+use log::{debug, warn};
+use smithay_client_toolkit::reexports::{
+    client::{protocol::wl_output::WlOutput, Connection, Dispatch, Proxy, QueueHandle},
+    protocols_wlr::output_power_management::v1::client::{
+        zwlr_output_power_manager_v1::ZwlrOutputPowerManagerV1,
+        zwlr_output_power_v1::{self, Mode, ZwlrOutputPowerV1},
+    },
+};
+
+use crate::DimData;
+
+/// Outputs we have turned off, turned back on once dropped
+pub struct PoweredOff {
+    powers: Vec<ZwlrOutputPowerV1>,
+}
+
+impl PoweredOff {
+    /// Turn the given outputs off.
+    pub fn new<'a>(
+        manager: &ZwlrOutputPowerManagerV1,
+        outputs: impl IntoIterator<Item = &'a WlOutput>,
+        qh: &QueueHandle<DimData>,
+    ) -> Self {
+        let powers = outputs
+            .into_iter()
+            .map(|output| {
+                let power = manager.get_output_power(output, qh, output.clone());
+                power.set_mode(Mode::Off);
+                power
+            })
+            .collect();
+
+        Self { powers }
+    }
+}
+
+impl Drop for PoweredOff {
+    fn drop(&mut self) {
+        // requests to controls which have failed, e.g. as their output is gone, are ignored
+        for power in self.powers.drain(..) {
+            power.set_mode(Mode::On);
+            power.destroy();
+        }
+    }
+}
+
+impl Dispatch<ZwlrOutputPowerV1, WlOutput> for DimData {
+    fn event(
+        _: &mut Self,
+        _: &ZwlrOutputPowerV1,
+        event: zwlr_output_power_v1::Event,
+        output: &WlOutput,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_output_power_v1::Event::Mode { mode } => {
+                debug!("Output {} is now {mode:?}", output.id());
+            }
+            zwlr_output_power_v1::Event::Failed => {
+                warn!("Failed to control the power of output {}", output.id());
+            }
+            _ => {}
+        }
+    }
+}
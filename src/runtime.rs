@@ -0,0 +1,43 @@
+use std::{
+    env,
+    fs::{self, DirBuilder},
+    io,
+    os::unix::fs::{DirBuilderExt, PermissionsExt},
+    path::{Path, PathBuf},
+};
+
+/// Our directory within `$XDG_RUNTIME_DIR`, if set. Holds records shared between instances.
+pub fn dir() -> Option<PathBuf> {
+    env::var_os("XDG_RUNTIME_DIR").map(|dir| PathBuf::from(dir).join("dim"))
+}
+
+/// Name of the display we use, Wayland or else X11, fit for naming files after it
+pub fn display() -> String {
+    env::var("WAYLAND_DISPLAY")
+        .or_else(|_| env::var("DISPLAY").map(|display| format!("x11{display}")))
+        .unwrap_or_else(|_| "wayland-0".to_owned())
+        .replace('/', "_")
+}
+
+/// Get our runtime directory, creating it readable only by the user if needed.
+pub fn create_dir() -> io::Result<PathBuf> {
+    let dir = dir().ok_or_else(|| io::Error::other("XDG_RUNTIME_DIR is not set"))?;
+    create(&dir)?;
+
+    Ok(dir)
+}
+
+/// Create the given directory, e.g. the one from [`dir`], readable only by the user if needed.
+pub fn create(dir: &Path) -> io::Result<()> {
+    DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+    fs::set_permissions(dir, fs::Permissions::from_mode(0o700))
+}
+
+/// When the process with the given PID started, in clock ticks since boot, if it is alive. Lets
+/// records tell whether the PID of the process which made them has since been reused.
+pub fn start_time(pid: u32) -> Option<u64> {
+    let stat = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    // the command name before this may contain spaces, the start time is the 22nd field
+    let (_, fields) = stat.rsplit_once(')')?;
+    fields.split_whitespace().nth(19)?.parse().ok()
+}
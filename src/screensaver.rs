@@ -0,0 +1,263 @@
+#[cfg(feature = "dbus")]
+pub use imp::ScreenSaver;
+
+#[cfg(feature = "dbus")]
+mod imp {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+        thread,
+    };
+
+    use anyhow::Result;
+    use log::{debug, info};
+    use zbus::{
+        blocking::{connection::Builder, fdo::DBusProxy, Connection},
+        interface,
+        message::Header,
+    };
+
+    /// A `org.freedesktop.ScreenSaver` service on the session bus, letting apps which do not set
+    /// idle inhibitors themselves, e.g. within Flatpak, keep us from dimming.
+    pub struct ScreenSaver {
+        _conn: Connection,
+        state: Arc<Mutex<State>>,
+    }
+
+    #[derive(Debug, Default)]
+    struct State {
+        /// Cookies of held inhibitors, along with the unique bus name of who holds them
+        inhibitors: HashMap<u32, String>,
+        last_cookie: u32,
+        active: bool,
+    }
+
+    impl ScreenSaver {
+        const NAME: &str = "org.freedesktop.ScreenSaver";
+        /// Apps use either path
+        const PATHS: [&str; 2] = ["/org/freedesktop/ScreenSaver", "/ScreenSaver"];
+
+        /// Own the service's name on the session bus, as given by `DBUS_SESSION_BUS_ADDRESS`, and
+        /// start serving it.
+        pub fn serve() -> Result<Self> {
+            Self::serve_with(Builder::session()?)
+        }
+
+        /// Serve on the bus the given builder is for, e.g. one of our own while testing.
+        fn serve_with(builder: Builder<'_>) -> Result<Self> {
+            let state = Arc::default();
+
+            let mut builder = builder.name(Self::NAME)?;
+            for path in Self::PATHS {
+                builder = builder.serve_at(path, Interface(Arc::clone(&state)))?;
+            }
+            let conn = builder.build()?;
+
+            // inhibitors of apps which have left the bus without uninhibiting are dropped
+            let owner_changes = DBusProxy::new(&conn)?.receive_name_owner_changed()?;
+            let weak_state = Arc::downgrade(&state);
+            thread::spawn(move || {
+                for signal in owner_changes {
+                    let (Ok(args), Some(state)) = (signal.args(), weak_state.upgrade()) else {
+                        continue;
+                    };
+
+                    if args.new_owner().is_none() {
+                        let mut state = state.lock().unwrap();
+                        state.inhibitors.retain(|cookie, owner| {
+                            let gone = owner.as_str() == args.name().as_str();
+                            if gone {
+                                debug!("Dropping inhibitor {cookie} of vanished {owner}");
+                            }
+                            !gone
+                        });
+                    }
+                }
+            });
+
+            Ok(Self { _conn: conn, state })
+        }
+
+        /// Whether any app holds an inhibitor
+        pub fn inhibited(&self) -> bool {
+            !self.state.lock().unwrap().inhibitors.is_empty()
+        }
+
+        /// Tell apps whether we are dimming, as the screensaver being active
+        pub fn set_active(&self, active: bool) {
+            self.state.lock().unwrap().active = active;
+        }
+    }
+
+    struct Interface(Arc<Mutex<State>>);
+
+    #[interface(name = "org.freedesktop.ScreenSaver")]
+    impl Interface {
+        fn inhibit(
+            &self,
+            application_name: String,
+            reason_for_inhibit: String,
+            #[zbus(header)] header: Header<'_>,
+        ) -> u32 {
+            let owner = header.sender().map(|s| s.to_string()).unwrap_or_default();
+            let mut state = self.0.lock().unwrap();
+            state.last_cookie = state.last_cookie.wrapping_add(1);
+
+            let cookie = state.last_cookie;
+            info!("{application_name} inhibited dimming with {cookie}: {reason_for_inhibit}");
+            state.inhibitors.insert(cookie, owner);
+            cookie
+        }
+
+        fn un_inhibit(&self, cookie: u32) {
+            if self.0.lock().unwrap().inhibitors.remove(&cookie).is_some() {
+                info!("Inhibitor {cookie} released");
+            }
+        }
+
+        fn get_active(&self) -> bool {
+            self.0.lock().unwrap().active
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::{
+            thread,
+            time::{Duration, Instant},
+        };
+
+        use zbus::blocking::Connection;
+
+        use super::ScreenSaver;
+        use crate::test_bus::TestBus;
+
+        /// How long the service may take to hear that an app has left the bus
+        const VANISH_TIMEOUT: Duration = Duration::from_secs(5);
+
+        fn inhibit(app: &Connection, path: &str) -> u32 {
+            app.call_method(
+                Some(ScreenSaver::NAME),
+                path,
+                Some(ScreenSaver::NAME),
+                "Inhibit",
+                &("test", "testing"),
+            )
+            .unwrap()
+            .body()
+            .deserialize()
+            .unwrap()
+        }
+
+        fn un_inhibit(app: &Connection, cookie: u32) {
+            app.call_method(
+                Some(ScreenSaver::NAME),
+                ScreenSaver::PATHS[0],
+                Some(ScreenSaver::NAME),
+                "UnInhibit",
+                &cookie,
+            )
+            .unwrap();
+        }
+
+        fn get_active(app: &Connection) -> bool {
+            app.call_method(
+                Some(ScreenSaver::NAME),
+                ScreenSaver::PATHS[0],
+                Some(ScreenSaver::NAME),
+                "GetActive",
+                &(),
+            )
+            .unwrap()
+            .body()
+            .deserialize()
+            .unwrap()
+        }
+
+        #[test]
+        fn inhibitors_held_until_released() {
+            let bus = TestBus::start();
+            let screensaver = ScreenSaver::serve_with(bus.builder()).unwrap();
+            let app = bus.builder().build().unwrap();
+            assert!(!screensaver.inhibited());
+
+            // apps use either path, sharing the same inhibitors
+            let first = inhibit(&app, ScreenSaver::PATHS[0]);
+            let second = inhibit(&app, ScreenSaver::PATHS[1]);
+            assert_ne!(first, second);
+            assert!(screensaver.inhibited());
+
+            un_inhibit(&app, first);
+            assert!(screensaver.inhibited());
+            // releasing twice, or a cookie never given, changes nothing
+            un_inhibit(&app, first);
+            un_inhibit(&app, second.wrapping_add(1));
+            assert!(screensaver.inhibited());
+
+            un_inhibit(&app, second);
+            assert!(!screensaver.inhibited());
+        }
+
+        #[test]
+        fn tells_whether_active() {
+            let bus = TestBus::start();
+            let screensaver = ScreenSaver::serve_with(bus.builder()).unwrap();
+            let app = bus.builder().build().unwrap();
+            assert!(!get_active(&app));
+
+            screensaver.set_active(true);
+            assert!(get_active(&app));
+
+            screensaver.set_active(false);
+            assert!(!get_active(&app));
+        }
+
+        #[test]
+        fn drops_inhibitors_of_vanished_apps() {
+            let bus = TestBus::start();
+            let screensaver = ScreenSaver::serve_with(bus.builder()).unwrap();
+            let staying = bus.builder().build().unwrap();
+            let leaving = bus.builder().build().unwrap();
+
+            inhibit(&leaving, ScreenSaver::PATHS[0]);
+            inhibit(&leaving, ScreenSaver::PATHS[1]);
+            drop(leaving);
+
+            let since = Instant::now();
+            while screensaver.inhibited() {
+                assert!(
+                    since.elapsed() < VANISH_TIMEOUT,
+                    "Inhibitors of a vanished app were kept"
+                );
+                thread::sleep(Duration::from_millis(10));
+            }
+
+            // those of apps still on the bus are kept
+            inhibit(&staying, ScreenSaver::PATHS[0]);
+            let leaving = bus.builder().build().unwrap();
+            inhibit(&leaving, ScreenSaver::PATHS[0]);
+            drop(leaving);
+            thread::sleep(Duration::from_millis(200));
+            assert!(screensaver.inhibited());
+        }
+    }
+}
+
+/// Stand-in for when we are built without D-Bus support, which can never be served.
+#[cfg(not(feature = "dbus"))]
+pub enum ScreenSaver {}
+
+#[cfg(not(feature = "dbus"))]
+impl ScreenSaver {
+    pub fn serve() -> anyhow::Result<Self> {
+        Err(anyhow::anyhow!("dim was built without the dbus feature"))
+    }
+
+    pub fn inhibited(&self) -> bool {
+        match *self {}
+    }
+
+    pub fn set_active(&self, _: bool) {
+        match *self {}
+    }
+}
@@ -0,0 +1,99 @@
+use smithay_client_toolkit::reexports::{
+    client::{
+        protocol::{
+            wl_keyboard::WlKeyboard, wl_pointer::WlPointer, wl_seat::WlSeat, wl_touch::WlTouch,
+        },
+        QueueHandle,
+    },
+    protocols::{
+        ext::idle_notify::v1::client::{
+            ext_idle_notification_v1::ExtIdleNotificationV1,
+            ext_idle_notifier_v1::ExtIdleNotifierV1,
+        },
+        wp::tablet::zv2::client::{
+            zwp_tablet_manager_v2::ZwpTabletManagerV2, zwp_tablet_seat_v2::ZwpTabletSeatV2,
+        },
+    },
+};
+
+use crate::{gestures::PointerGestures, idle::IdleWatch, DimData};
+
+/// Input devices we have bound from a single seat, released once dropped
+#[derive(Default)]
+pub struct SeatDevices {
+    pub keyboard: Option<WlKeyboard>,
+    pub pointer: Option<WlPointer>,
+    pub gestures: Option<PointerGestures>,
+    pub touch: Option<WlTouch>,
+    pub tablet_seat: Option<ZwpTabletSeatV2>,
+    /// Used to learn about activity we do not receive input for, and about idle inhibitors
+    pub idle_notifications: Vec<ExtIdleNotificationV1>,
+    /// Whether the compositor considers this seat idle, respecting idle inhibitors
+    pub idle: bool,
+    /// Whether the compositor has seen no input on this seat, ignoring idle inhibitors
+    pub input_idle: bool,
+}
+
+impl SeatDevices {
+    /// Prepare to track the devices of a new seat, watching its idleness in the given ways. Input
+    /// watches should come first, so the compositor tells us about input before its effect on
+    /// idleness.
+    pub fn new(
+        tablet_manager: Option<&ZwpTabletManagerV2>,
+        idle: Option<(&ExtIdleNotifierV1, &[IdleWatch])>,
+        seat: &WlSeat,
+        qh: &QueueHandle<DimData>,
+    ) -> Self {
+        Self {
+            keyboard: None,
+            pointer: None,
+            gestures: None,
+            touch: None,
+            tablet_seat: tablet_manager
+                .map(|manager| manager.get_tablet_seat(seat, qh, seat.clone())),
+            idle_notifications: idle
+                .map(|(notifier, watches)| {
+                    watches
+                        .iter()
+                        .map(|watch| watch.notification(notifier, seat, qh))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            idle: false,
+            input_idle: false,
+        }
+    }
+
+    pub fn release_keyboard(&mut self) {
+        if let Some(keyboard) = self.keyboard.take() {
+            keyboard.release();
+        }
+    }
+
+    pub fn release_pointer(&mut self) {
+        self.gestures = None;
+        if let Some(pointer) = self.pointer.take() {
+            pointer.release();
+        }
+    }
+
+    pub fn release_touch(&mut self) {
+        if let Some(touch) = self.touch.take() {
+            touch.release();
+        }
+    }
+}
+
+impl Drop for SeatDevices {
+    fn drop(&mut self) {
+        self.release_keyboard();
+        self.release_pointer();
+        self.release_touch();
+        if let Some(tablet_seat) = self.tablet_seat.take() {
+            tablet_seat.destroy();
+        }
+        for notification in self.idle_notifications.drain(..) {
+            notification.destroy();
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use std::{io, os::unix::process::CommandExt, process::Command, ptr};
+
+use anyhow::Result;
+use log::{debug, info};
+use smithay_client_toolkit::reexports::calloop::{
+    signals::{Signal, Signals},
+    LoopHandle,
+};
+
+use crate::control::{Controlled, Request};
+
+/// Handle signals within the event loop:
+///
+/// - `SIGTERM` and `SIGINT` end with [`DimOutcome::Interrupted`](crate::DimOutcome::Interrupted)
+/// - `SIGUSR1` extends the current dim by `extend_by` seconds
+/// - `SIGUSR2` ends the current dim as if its duration passed
+///
+/// Signals are only blocked on this thread and those it spawns afterwards, so this should be
+/// called before any other thread is spawned.
+pub fn listen<D: Controlled + 'static>(
+    handle: &LoopHandle<'static, D>,
+    extend_by: u64,
+) -> Result<()> {
+    let signals = Signals::new(&[
+        Signal::SIGTERM,
+        Signal::SIGINT,
+        Signal::SIGUSR1,
+        Signal::SIGUSR2,
+    ])?;
+
+    handle
+        .insert_source(signals, move |event, _, data: &mut D| {
+            let signal = event.signal();
+            debug!("Received {signal}");
+
+            let result = match signal {
+                Signal::SIGUSR1 => data.handle_request(Request::Extend(extend_by)),
+                Signal::SIGUSR2 => data.handle_request(Request::Finish),
+                _ => {
+                    data.interrupt();
+                    return;
+                }
+            };
+
+            if let Err(e) = result {
+                info!("Ignoring {signal}: {e}");
+            }
+        })
+        .map_err(|e| e.error)?;
+
+    Ok(())
+}
+
+/// Let the given command receive the signals we block, as children inherit our signal mask.
+pub fn unblock_in_child(command: &mut Command) -> &mut Command {
+    // SAFETY: only async-signal-safe functions are called between fork and exec
+    unsafe {
+        command.pre_exec(|| {
+            let mut set = std::mem::zeroed();
+            libc::sigemptyset(&mut set);
+            match libc::pthread_sigmask(libc::SIG_SETMASK, &set, ptr::null_mut()) {
+                0 => Ok(()),
+                e => Err(io::Error::from_raw_os_error(e)),
+            }
+        })
+    }
+}
@@ -0,0 +1,242 @@
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use log::debug;
+
+use crate::{
+    backend::Level,
+    buffer::Color,
+    control::Request,
+    events::{Event, EventCallback, Fade},
+    hooks::Report,
+    timeline::Timeline,
+    DimOpts, DimOutcome, EventFormat, InputClass,
+};
+
+/// The course of a dim on any display: its fade and duration, how it ends, and the events
+/// telling about it. Driven by [`DimData`](crate::DimData) on Wayland, and by the X11 dim.
+pub(crate) struct DimState {
+    pub timeline: Timeline,
+    pub color: Color,
+    fade_out_sec: f32,
+    /// Whether fading out before ending with [`DimOutcome::Interrupted`]
+    fading_out: bool,
+    pub outcome: Option<DimOutcome>,
+    /// What cancelled the dim, if anything did
+    pub cancel_reason: Option<&'static str>,
+
+    events: Option<EventFormat>,
+    /// Called with every event, see [`DimData::on_event`](crate::DimData::on_event)
+    pub on_event: Option<EventCallback>,
+}
+
+/// What a dim is doing, as answered to [`Request::Status`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Status {
+    Dimming,
+    /// Waiting for the locker to lock the session
+    Locking,
+    /// Outputs are off until the user returns
+    Off,
+    /// Waiting for inhibitors to clear
+    Inhibited,
+    Idle,
+}
+
+impl Status {
+    fn name(self) -> &'static str {
+        match self {
+            Status::Dimming => "dimming",
+            Status::Locking => "locking",
+            Status::Off => "off",
+            Status::Inhibited => "inhibited",
+            Status::Idle => "idle",
+        }
+    }
+}
+
+impl DimState {
+    pub fn new(opts: &DimOpts) -> Self {
+        Self {
+            timeline: Timeline::new(
+                opts.alpha(),
+                opts.fade(),
+                // We consider a duration of 0 as infinite
+                Some(opts.duration())
+                    .filter(|&d| d > 0)
+                    .map(Duration::from_secs),
+            ),
+            color: opts.color.unwrap_or_default(),
+            fade_out_sec: opts.fade_out(),
+            fading_out: false,
+            outcome: None,
+            cancel_reason: None,
+
+            events: opts.events,
+            on_event: None,
+        }
+    }
+
+    pub fn emit(&self, event: Event) {
+        if let Some(callback) = &self.on_event {
+            callback(&event);
+        }
+        if let Some(format) = self.events {
+            event.emit(format);
+        }
+    }
+
+    /// Fade in from now, for the whole duration.
+    pub fn start(&mut self) {
+        self.timeline.start();
+        self.fading_out = false;
+        self.emit(Event::FadeStarted {
+            direction: Fade::In,
+        });
+    }
+
+    /// Stop fading, as the dim is no longer shown.
+    pub fn stop(&mut self) {
+        self.timeline.end_ramp();
+        self.fading_out = false;
+    }
+
+    /// Whether the dim may still end in any way, having no outcome and not fading out. Says
+    /// nothing about whether it is shown.
+    pub fn running(&self) -> bool {
+        self.outcome.is_none() && !self.fading_out
+    }
+
+    /// Time left until the dim completes, if it is not infinite or fading out.
+    pub fn time_left(&self) -> Option<Duration> {
+        self.timeline.time_left().filter(|_| !self.fading_out)
+    }
+
+    /// Time left until the fade-out ends, if we are fading out.
+    pub fn fade_out_left(&self) -> Option<Duration> {
+        self.timeline.ramp_left().filter(|_| self.fading_out)
+    }
+
+    /// Level the backends should show by now, telling once the fade-in is done.
+    pub fn step(&mut self) -> Level {
+        if self.timeline.take_fade_done() {
+            debug!("Fade done!");
+            self.emit(Event::FadeFinished {
+                direction: Fade::In,
+            });
+        }
+
+        self.level()
+    }
+
+    /// Level the backends should show by now
+    pub fn level(&self) -> Level {
+        Level {
+            alpha: self.timeline.shown_alpha(),
+            color: self.color,
+            elapsed: self.timeline.elapsed(),
+        }
+    }
+
+    /// End with [`DimOutcome::Interrupted`] once the fade-out is done.
+    pub fn poll_fade_out(&mut self) {
+        if self.fade_out_left() == Some(Duration::ZERO) {
+            debug!("Fade-out done!");
+            self.emit(Event::FadeFinished {
+                direction: Fade::Out,
+            });
+            self.outcome = Some(DimOutcome::Interrupted);
+        }
+    }
+
+    /// End the dim because of the given input, telling which key was pressed if any.
+    pub fn cancel(&mut self, class: InputClass, keysym: Option<String>) {
+        self.cancel_reason = Some(class.name());
+        self.emit(Event::Input { class, keysym });
+        self.outcome = Some(DimOutcome::Cancelled);
+    }
+
+    /// Take the outcome once the dim has ended, telling it to hooks with the given report.
+    pub fn take_outcome(&mut self, report: Report) -> Option<(DimOutcome, Report)> {
+        let outcome = self.outcome.take()?;
+        let cancel_reason = self.cancel_reason.take();
+        let cancel_reason = match outcome {
+            DimOutcome::Completed | DimOutcome::PoweredOff => None,
+            DimOutcome::Cancelled => cancel_reason.or(Some("input")),
+            DimOutcome::Inhibited | DimOutcome::Exempted | DimOutcome::Interrupted => {
+                Some(outcome.name())
+            }
+        };
+
+        let report = Report {
+            outcome: Some(outcome),
+            cancel_reason,
+            ..report
+        };
+        Some((outcome, report))
+    }
+
+    /// Act on a request sent through the control socket or by a signal while doing what the
+    /// given status tells, answering it.
+    pub fn handle_request(&mut self, request: Request, status: Status) -> Result<String> {
+        let running = status == Status::Dimming;
+        match request {
+            Request::Status => {
+                let remaining = match self.time_left() {
+                    Some(left) if running => format!("{:.1}", left.as_secs_f32()),
+                    None if running => "infinite".to_owned(),
+                    _ => "none".to_owned(),
+                };
+
+                Ok(format!(
+                    "state={} remaining={remaining} alpha={} color={}",
+                    status.name(),
+                    self.timeline.alpha(),
+                    self.color
+                ))
+            }
+            Request::Cancel | Request::Finish | Request::Extend(_) if !running => {
+                bail!("Not dimming")
+            }
+            Request::Cancel => {
+                self.cancel_reason = Some("request");
+                self.outcome = Some(DimOutcome::Cancelled);
+                Ok("ok".to_owned())
+            }
+            Request::Finish => {
+                self.timeline.finish();
+                Ok("ok".to_owned())
+            }
+            Request::Extend(secs) => {
+                let Some(left) = self.timeline.extend(Duration::from_secs(secs)) else {
+                    bail!("The dim has no duration to extend");
+                };
+                Ok(format!("remaining={:.1}", left.as_secs_f32()))
+            }
+            Request::SetAlpha(alpha) => {
+                self.timeline.set_alpha(alpha);
+                Ok("ok".to_owned())
+            }
+            Request::SetColor(color) => {
+                self.color = color;
+                Ok("ok".to_owned())
+            }
+        }
+    }
+
+    /// Stop because of SIGTERM or SIGINT, fading out first if desired and the dim is running.
+    /// Being interrupted again while fading out stops right away.
+    pub fn interrupt(&mut self, running: bool) {
+        if !running || self.fade_out_sec <= 0. {
+            self.outcome = Some(DimOutcome::Interrupted);
+            return;
+        }
+
+        debug!("Fading out");
+        self.emit(Event::FadeStarted {
+            direction: Fade::Out,
+        });
+        self.fading_out = true;
+        self.timeline.ramp_to(0., self.fade_out_sec);
+    }
+}
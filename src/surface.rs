@@ -1,32 +1,81 @@
 use smithay_client_toolkit::{
-    reexports::{client::QueueHandle, protocols::wp::viewporter::client::wp_viewport::WpViewport},
-    shell::{wlr_layer::LayerSurface, WaylandSurface},
+    reexports::{
+        client::{protocol::wl_surface::WlSurface, QueueHandle},
+        protocols::wp::viewporter::client::wp_viewport::WpViewport,
+    },
+    shell::{wlr_layer::LayerSurface, xdg::window::Window, WaylandSurface},
 };
 
-use crate::{buffer::BufferType, DimData};
+use crate::{
+    buffer::{BufferManager, BufferType, Color},
+    DimData,
+};
 
 pub struct DimSurface {
     buffer: BufferType,
     back_buffer: BufferType,
 
     viewport: WpViewport,
-    layer: LayerSurface,
+    surface: ShellSurface,
+
+    /// Whether the compositor has configured the surface, letting us draw
+    configured: bool,
+    /// Whether we are waiting for a frame before drawing again
+    frame_pending: bool,
+    /// Alpha and color last drawn
+    shown: Option<(f32, Color)>,
 }
 
 impl DimSurface {
     pub fn new(
-        _qh: &QueueHandle<DimData>,
         buffer: BufferType,
         back_buffer: BufferType,
         viewport: WpViewport,
-        layer: LayerSurface,
+        surface: ShellSurface,
     ) -> Self {
         Self {
             buffer,
             back_buffer,
             viewport,
-            layer,
+            surface,
+            configured: false,
+            frame_pending: false,
+            shown: None,
+        }
+    }
+
+    /// Draw the given alpha and color unless already shown, or unless waiting for a frame, at
+    /// which the latest ones are to be shown instead.
+    pub fn show(
+        &mut self,
+        qh: &QueueHandle<DimData>,
+        buffer_mgr: &mut BufferManager,
+        alpha: f32,
+        color: Color,
+    ) {
+        if !self.configured || self.frame_pending || self.shown == Some((alpha, color)) {
+            return;
+        }
+
+        buffer_mgr.refill(qh, &mut self.back_buffer, color, alpha);
+        self.draw(qh, true);
+        self.shown = Some((alpha, color));
+    }
+
+    /// The compositor configured the surface, to the given size if it chose one, which must be
+    /// drawn anew.
+    pub fn configure(&mut self, size: Option<(i32, i32)>) {
+        if let Some((width, height)) = size {
+            self.set_size(width, height);
         }
+        self.configured = true;
+        self.frame_pending = false;
+        self.shown = None;
+    }
+
+    /// The compositor is ready for us to draw again.
+    pub fn frame_done(&mut self) {
+        self.frame_pending = false;
     }
 
     pub fn draw(&mut self, qh: &QueueHandle<DimData>, request_next: bool) {
@@ -35,21 +84,21 @@ impl DimSurface {
             BufferType::Shared(buffer) => buffer.wl_buffer(),
         };
 
-        self.layer.wl_surface().attach(Some(wl_buffer), 0, 0);
-        self.layer.wl_surface().damage_buffer(0, 0, 1, 1);
+        let surface = self.surface.wl_surface();
+        surface.attach(Some(wl_buffer), 0, 0);
+        surface.damage_buffer(0, 0, 1, 1);
         std::mem::swap(&mut self.buffer, &mut self.back_buffer);
 
         if request_next {
-            self.layer
-                .wl_surface()
-                .frame(qh, self.layer.wl_surface().clone());
+            surface.frame(qh, surface.clone());
+            self.frame_pending = true;
         }
 
-        self.layer.commit();
+        surface.commit();
     }
 
-    pub fn layer(&self) -> &LayerSurface {
-        &self.layer
+    pub fn wl_surface(&self) -> &WlSurface {
+        self.surface.wl_surface()
     }
 
     pub fn set_size(&mut self, width: i32, height: i32) {
@@ -65,6 +114,22 @@ impl DimSurface {
     }
 }
 
+/// Role of a [`DimSurface`], depending on the [`Shell`](crate::Shell) we have
+pub enum ShellSurface {
+    Layer(LayerSurface),
+    /// A fullscreen window, when we have no layer shell
+    Xdg(Window),
+}
+
+impl ShellSurface {
+    pub fn wl_surface(&self) -> &WlSurface {
+        match self {
+            ShellSurface::Layer(layer) => layer.wl_surface(),
+            ShellSurface::Xdg(window) => window.wl_surface(),
+        }
+    }
+}
+
 impl Drop for DimSurface {
     fn drop(&mut self) {
         self.viewport.destroy();
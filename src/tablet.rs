@@ -0,0 +1,194 @@
+use std::sync::OnceLock;
+
+use log::debug;
+use smithay_client_toolkit::reexports::{
+    client::{
+        event_created_child, protocol::wl_seat::WlSeat, Connection, Dispatch, Proxy, QueueHandle,
+    },
+    protocols::wp::tablet::zv2::client::{
+        zwp_tablet_pad_group_v2::{self, ZwpTabletPadGroupV2},
+        zwp_tablet_pad_ring_v2::{self, ZwpTabletPadRingV2},
+        zwp_tablet_pad_strip_v2::{self, ZwpTabletPadStripV2},
+        zwp_tablet_pad_v2::{self, ZwpTabletPadV2},
+        zwp_tablet_seat_v2::{self, ZwpTabletSeatV2},
+        zwp_tablet_tool_v2::{self, ZwpTabletToolV2},
+        zwp_tablet_v2::{self, ZwpTabletV2},
+    },
+};
+
+use crate::{dim::InputClass, DimData};
+
+/// Data of objects created by the tablet seat, which only learn which seat they belong to once
+/// their creation event is handled.
+#[derive(Debug, Default)]
+pub struct TabletData(OnceLock<WlSeat>);
+
+impl TabletData {
+    fn seat(&self) -> Option<&WlSeat> {
+        self.0.get()
+    }
+
+    /// Pass the given seat on to a newly created object
+    fn set_seat<I: Proxy>(proxy: &I, seat: Option<&WlSeat>) {
+        if let (Some(data), Some(seat)) = (proxy.data::<Self>(), seat) {
+            let _ = data.0.set(seat.clone());
+        }
+    }
+}
+
+impl Dispatch<ZwpTabletSeatV2, WlSeat> for DimData {
+    fn event(
+        _: &mut Self,
+        _: &ZwpTabletSeatV2,
+        event: zwp_tablet_seat_v2::Event,
+        seat: &WlSeat,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwp_tablet_seat_v2::Event::TabletAdded { .. } => debug!("Tablet added"),
+            zwp_tablet_seat_v2::Event::ToolAdded { id } => {
+                debug!("Tablet tool added");
+                TabletData::set_seat(&id, Some(seat));
+            }
+            zwp_tablet_seat_v2::Event::PadAdded { id } => {
+                debug!("Tablet pad added");
+                TabletData::set_seat(&id, Some(seat));
+            }
+            _ => {}
+        }
+    }
+
+    event_created_child!(DimData, ZwpTabletSeatV2, [
+        zwp_tablet_seat_v2::EVT_TABLET_ADDED_OPCODE => (ZwpTabletV2, ()),
+        zwp_tablet_seat_v2::EVT_TOOL_ADDED_OPCODE => (ZwpTabletToolV2, TabletData::default()),
+        zwp_tablet_seat_v2::EVT_PAD_ADDED_OPCODE => (ZwpTabletPadV2, TabletData::default()),
+    ]);
+}
+
+impl Dispatch<ZwpTabletV2, ()> for DimData {
+    fn event(
+        _: &mut Self,
+        tablet: &ZwpTabletV2,
+        event: zwp_tablet_v2::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let zwp_tablet_v2::Event::Removed = event {
+            tablet.destroy();
+        }
+    }
+}
+
+impl Dispatch<ZwpTabletToolV2, TabletData> for DimData {
+    fn event(
+        state: &mut Self,
+        tool: &ZwpTabletToolV2,
+        event: zwp_tablet_tool_v2::Event,
+        data: &TabletData,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwp_tablet_tool_v2::Event::ProximityIn { .. }
+            | zwp_tablet_tool_v2::Event::Down { .. }
+            | zwp_tablet_tool_v2::Event::Motion { .. }
+            | zwp_tablet_tool_v2::Event::Button { .. } => {
+                if let Some(seat) = data.seat() {
+                    state.cancel(seat, InputClass::Tablet);
+                }
+            }
+            zwp_tablet_tool_v2::Event::Removed => tool.destroy(),
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ZwpTabletPadV2, TabletData> for DimData {
+    fn event(
+        state: &mut Self,
+        pad: &ZwpTabletPadV2,
+        event: zwp_tablet_pad_v2::Event,
+        data: &TabletData,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwp_tablet_pad_v2::Event::Group { pad_group } => {
+                TabletData::set_seat(&pad_group, data.seat());
+            }
+            zwp_tablet_pad_v2::Event::Button { .. } => {
+                if let Some(seat) = data.seat() {
+                    state.cancel(seat, InputClass::Tablet);
+                }
+            }
+            zwp_tablet_pad_v2::Event::Removed => pad.destroy(),
+            _ => {}
+        }
+    }
+
+    event_created_child!(DimData, ZwpTabletPadV2, [
+        zwp_tablet_pad_v2::EVT_GROUP_OPCODE => (ZwpTabletPadGroupV2, TabletData::default()),
+    ]);
+}
+
+impl Dispatch<ZwpTabletPadGroupV2, TabletData> for DimData {
+    fn event(
+        _: &mut Self,
+        group: &ZwpTabletPadGroupV2,
+        event: zwp_tablet_pad_group_v2::Event,
+        data: &TabletData,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwp_tablet_pad_group_v2::Event::Ring { ring } => {
+                TabletData::set_seat(&ring, data.seat());
+            }
+            zwp_tablet_pad_group_v2::Event::Strip { strip } => {
+                TabletData::set_seat(&strip, data.seat());
+            }
+            zwp_tablet_pad_group_v2::Event::ModeSwitch { .. } => {
+                debug!("Mode switched on pad group {}", group.id());
+            }
+            _ => {}
+        }
+    }
+
+    event_created_child!(DimData, ZwpTabletPadGroupV2, [
+        zwp_tablet_pad_group_v2::EVT_RING_OPCODE => (ZwpTabletPadRingV2, TabletData::default()),
+        zwp_tablet_pad_group_v2::EVT_STRIP_OPCODE => (ZwpTabletPadStripV2, TabletData::default()),
+    ]);
+}
+
+impl Dispatch<ZwpTabletPadRingV2, TabletData> for DimData {
+    fn event(
+        state: &mut Self,
+        _: &ZwpTabletPadRingV2,
+        event: zwp_tablet_pad_ring_v2::Event,
+        data: &TabletData,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let (zwp_tablet_pad_ring_v2::Event::Angle { .. }, Some(seat)) = (event, data.seat()) {
+            state.cancel(seat, InputClass::Tablet);
+        }
+    }
+}
+
+impl Dispatch<ZwpTabletPadStripV2, TabletData> for DimData {
+    fn event(
+        state: &mut Self,
+        _: &ZwpTabletPadStripV2,
+        event: zwp_tablet_pad_strip_v2::Event,
+        data: &TabletData,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let (zwp_tablet_pad_strip_v2::Event::Position { .. }, Some(seat)) = (event, data.seat())
+        {
+            state.cancel(seat, InputClass::Tablet);
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use std::{
+    io::{BufRead, BufReader},
+    process::{Child, Command, Stdio},
+};
+
+use zbus::blocking::connection::Builder;
+
+/// A session bus of our own for tests, so they neither need nor disturb the user's. Killed once
+/// dropped.
+pub struct TestBus {
+    daemon: Child,
+    address: String,
+}
+
+impl TestBus {
+    pub fn start() -> Self {
+        let mut daemon = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("dbus-daemon is needed to test what we do on D-Bus");
+
+        let stdout = daemon.stdout.take().expect("stdout is piped");
+        let mut address = String::new();
+        BufReader::new(stdout)
+            .read_line(&mut address)
+            .expect("dbus-daemon did not print its address");
+
+        Self {
+            daemon,
+            address: address.trim_end().to_owned(),
+        }
+    }
+
+    /// Start connecting to this bus
+    pub fn builder(&self) -> Builder<'static> {
+        Builder::address(self.address.as_str()).expect("dbus-daemon printed an invalid address")
+    }
+}
+
+impl Drop for TestBus {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+    }
+}
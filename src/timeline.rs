@@ -0,0 +1,276 @@
+use std::time::{Duration, Instant};
+
+/// The alpha of a dim over time: faded in from nothing to the desired alpha, then possibly
+/// ramped to another, e.g. faded out. Also keeps the time left until the dim completes.
+#[derive(Debug, Clone, Copy)]
+pub struct Timeline {
+    alpha: f32,
+    fade_sec: f32,
+    start_time: Instant,
+    fade_done: bool,
+    /// Animation of the alpha after the fade-in, e.g. the fade-out
+    ramp: Option<Ramp>,
+    /// How long the dim lasts, `None` being infinite
+    duration: Option<Duration>,
+    /// Added to the duration since the fade-in started, e.g. by SIGUSR1
+    extension: Duration,
+    /// Whether the dim should complete now, as if its duration passed
+    finished: bool,
+}
+
+/// An animation of the alpha after the fade-in
+#[derive(Debug, Clone, Copy)]
+struct Ramp {
+    since: Instant,
+    from: f32,
+    to: f32,
+    secs: f32,
+}
+
+impl Ramp {
+    fn progress(&self) -> f32 {
+        match self.secs > 0. {
+            true => (self.since.elapsed().as_secs_f32() / self.secs).min(1.),
+            false => 1.,
+        }
+    }
+
+    fn alpha(&self) -> f32 {
+        self.from + (self.to - self.from) * self.progress()
+    }
+
+    fn left(&self) -> Duration {
+        Duration::from_secs_f32(self.secs).saturating_sub(self.since.elapsed())
+    }
+}
+
+impl Timeline {
+    /// Fade in to the given alpha over the given seconds once started, completing after the
+    /// given duration if any.
+    pub fn new(alpha: f32, fade_sec: f32, duration: Option<Duration>) -> Self {
+        Self {
+            alpha,
+            fade_sec,
+            start_time: Instant::now(),
+            fade_done: false,
+            ramp: None,
+            duration,
+            extension: Duration::ZERO,
+            finished: false,
+        }
+    }
+
+    /// Fade in again from now, for the whole duration.
+    pub fn start(&mut self) {
+        self.start_time = Instant::now();
+        self.fade_done = false;
+        self.ramp = None;
+        self.extension = Duration::ZERO;
+        self.finished = false;
+    }
+
+    /// Time left until the dim completes, if it is not infinite.
+    pub fn time_left(&self) -> Option<Duration> {
+        if self.finished {
+            return Some(Duration::ZERO);
+        }
+
+        self.duration
+            .map(|d| (d + self.extension).saturating_sub(self.elapsed()))
+    }
+
+    /// Add to the duration, returning the time now left, or `None` if it is infinite.
+    pub fn extend(&mut self, by: Duration) -> Option<Duration> {
+        self.duration?;
+        self.extension += by;
+        self.time_left()
+    }
+
+    /// Complete the dim now, as if its duration passed.
+    pub fn finish(&mut self) {
+        self.finished = true;
+    }
+
+    /// Time since the fade-in started
+    pub fn elapsed(&self) -> Duration {
+        self.start_time.elapsed()
+    }
+
+    /// Alpha we fade in to
+    pub fn alpha(&self) -> f32 {
+        self.alpha
+    }
+
+    pub fn set_alpha(&mut self, alpha: f32) {
+        self.alpha = alpha;
+    }
+
+    pub fn fade_sec(&self) -> f32 {
+        self.fade_sec
+    }
+
+    /// Whether the fade-in has just finished, only true the first time it is asked once it has.
+    pub fn take_fade_done(&mut self) -> bool {
+        if self.fade_done
+            || self.ramp.is_some()
+            || self.start_time.elapsed().as_secs_f32() < self.fade_sec
+        {
+            return false;
+        }
+
+        self.fade_done = true;
+        true
+    }
+
+    /// Animate the alpha from what is shown now to the given one.
+    pub fn ramp_to(&mut self, to: f32, secs: f32) {
+        self.ramp = Some(Ramp {
+            since: Instant::now(),
+            from: self.shown_alpha(),
+            to,
+            secs,
+        });
+    }
+
+    /// Stop animating towards the alpha given to [`Timeline::ramp_to`].
+    pub fn end_ramp(&mut self) {
+        self.ramp = None;
+    }
+
+    /// Time left until the alpha given to [`Timeline::ramp_to`] is reached, if ramping.
+    pub fn ramp_left(&self) -> Option<Duration> {
+        self.ramp.map(|ramp| ramp.left())
+    }
+
+    /// Whether the alpha is still changing, be it from the fade-in or a ramp
+    pub fn animating(&self) -> bool {
+        match self.ramp {
+            Some(ramp) => ramp.progress() < 1.,
+            None => !self.fade_done,
+        }
+    }
+
+    /// Alpha to show by now
+    pub fn shown_alpha(&self) -> f32 {
+        if let Some(ramp) = self.ramp {
+            return ramp.alpha();
+        }
+
+        match self.fade_done || self.fade_sec <= 0. {
+            true => self.alpha,
+            false => {
+                let elapsed_sec = self.start_time.elapsed().as_millis() as f32 / 1000.;
+                (self.alpha * (elapsed_sec / self.fade_sec)).clamp(0., self.alpha)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Leeway for the time passing while a test runs
+    const EPSILON: f32 = 0.05;
+
+    fn assert_near(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < EPSILON,
+            "{actual} is not near {expected}"
+        );
+    }
+
+    /// A timeline started the given seconds ago
+    fn started_ago(alpha: f32, fade_sec: f32, duration: Option<u64>, secs: f32) -> Timeline {
+        let mut timeline = Timeline::new(alpha, fade_sec, duration.map(Duration::from_secs));
+        timeline.start();
+        timeline.start_time -= Duration::from_secs_f32(secs);
+        timeline
+    }
+
+    #[test]
+    fn fades_in() {
+        let mut timeline = started_ago(0.8, 2., None, 1.);
+        assert_near(timeline.shown_alpha(), 0.4);
+        assert!(timeline.animating());
+        assert!(!timeline.take_fade_done());
+
+        timeline.start_time -= Duration::from_secs(2);
+        assert_near(timeline.shown_alpha(), 0.8);
+        assert!(timeline.take_fade_done());
+        assert!(!timeline.take_fade_done());
+        assert!(!timeline.animating());
+    }
+
+    #[test]
+    fn shows_alpha_without_fade() {
+        let timeline = started_ago(0.8, 0., None, 0.);
+        assert_eq!(timeline.shown_alpha(), 0.8);
+    }
+
+    #[test]
+    fn ramps_from_shown_alpha() {
+        let mut timeline = started_ago(0.8, 2., None, 1.);
+        timeline.ramp_to(0., 1.);
+        let ramp = timeline.ramp.as_mut().unwrap();
+        ramp.since -= Duration::from_secs_f32(0.5);
+
+        assert_near(timeline.shown_alpha(), 0.2);
+        assert_near(timeline.ramp_left().unwrap().as_secs_f32(), 0.5);
+        // the fade-in is over once ramping
+        assert!(!timeline.take_fade_done());
+
+        timeline.end_ramp();
+        assert_eq!(timeline.ramp_left(), None);
+    }
+
+    #[test]
+    fn ramp_ends_at_target() {
+        let mut timeline = started_ago(0.5, 0., None, 1.);
+        timeline.ramp_to(1., 0.);
+        assert_eq!(timeline.shown_alpha(), 1.);
+        assert_eq!(timeline.ramp_left(), Some(Duration::ZERO));
+        assert!(!timeline.animating());
+    }
+
+    #[test]
+    fn counts_down_duration() {
+        let timeline = started_ago(0.5, 0., Some(10), 4.);
+        assert_near(timeline.time_left().unwrap().as_secs_f32(), 6.);
+
+        let timeline = started_ago(0.5, 0., Some(10), 11.);
+        assert_eq!(timeline.time_left(), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn extends_duration() {
+        let mut timeline = started_ago(0.5, 0., Some(10), 4.);
+        let left = timeline.extend(Duration::from_secs(5)).unwrap();
+        assert_near(left.as_secs_f32(), 11.);
+        assert_near(timeline.time_left().unwrap().as_secs_f32(), 11.);
+
+        // extensions only last for the dim they were made in
+        timeline.start();
+        assert_near(timeline.time_left().unwrap().as_secs_f32(), 10.);
+    }
+
+    #[test]
+    fn infinite_duration_cannot_be_extended() {
+        let mut timeline = started_ago(0.5, 0., None, 4.);
+        assert_eq!(timeline.time_left(), None);
+        assert_eq!(timeline.extend(Duration::from_secs(5)), None);
+        assert_eq!(timeline.time_left(), None);
+    }
+
+    #[test]
+    fn finishes_early() {
+        for duration in [Some(10), None] {
+            let mut timeline = started_ago(0.5, 0., duration, 1.);
+            timeline.finish();
+            assert_eq!(timeline.time_left(), Some(Duration::ZERO));
+
+            timeline.start();
+            assert_ne!(timeline.time_left(), Some(Duration::ZERO));
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use std::sync::Mutex;
+
+use log::debug;
+use smithay_client_toolkit::reexports::{
+    client::{event_created_child, Connection, Dispatch, QueueHandle},
+    protocols_wlr::foreign_toplevel::v1::client::{
+        zwlr_foreign_toplevel_handle_v1::{self, State, ZwlrForeignToplevelHandleV1},
+        zwlr_foreign_toplevel_manager_v1::{self, ZwlrForeignToplevelManagerV1},
+    },
+};
+
+use crate::{glob, DimData};
+
+/// Which focused toplevels should keep us from dimming
+#[derive(Debug, Default)]
+pub struct Exemptions {
+    pub skip_if_fullscreen: bool,
+    /// Globs where `*` matches any run of characters and `?` any single one
+    pub app_ids: Vec<String>,
+}
+
+impl Exemptions {
+    pub fn is_empty(&self) -> bool {
+        !self.skip_if_fullscreen && self.app_ids.is_empty()
+    }
+
+    /// Whether the given toplevel is focused and exempted
+    pub fn exempts(&self, toplevel: &ToplevelState) -> bool {
+        if !toplevel.activated {
+            return false;
+        }
+
+        (self.skip_if_fullscreen && toplevel.fullscreen)
+            || toplevel
+                .app_id
+                .as_deref()
+                .is_some_and(|app_id| self.app_ids.iter().any(|glob| glob::matches(glob, app_id)))
+    }
+}
+
+/// What we know of a toplevel, updated as the compositor describes it
+#[derive(Debug, Default, Clone)]
+pub struct ToplevelState {
+    pub app_id: Option<String>,
+    pub activated: bool,
+    pub fullscreen: bool,
+}
+
+/// Data of a toplevel handle, holding its state as last described by the compositor
+#[derive(Debug, Default)]
+pub struct ToplevelData(Mutex<ToplevelState>);
+
+impl ToplevelData {
+    pub fn state(&self) -> ToplevelState {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+impl Dispatch<ZwlrForeignToplevelManagerV1, ()> for DimData {
+    fn event(
+        state: &mut Self,
+        _: &ZwlrForeignToplevelManagerV1,
+        event: zwlr_foreign_toplevel_manager_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_foreign_toplevel_manager_v1::Event::Toplevel { toplevel } => {
+                state.toplevel_added(toplevel);
+            }
+            zwlr_foreign_toplevel_manager_v1::Event::Finished => {
+                debug!("Foreign toplevel manager finished");
+            }
+            _ => {}
+        }
+    }
+
+    event_created_child!(DimData, ZwlrForeignToplevelManagerV1, [
+        zwlr_foreign_toplevel_manager_v1::EVT_TOPLEVEL_OPCODE => (ZwlrForeignToplevelHandleV1, ToplevelData::default()),
+    ]);
+}
+
+impl Dispatch<ZwlrForeignToplevelHandleV1, ToplevelData> for DimData {
+    fn event(
+        state: &mut Self,
+        handle: &ZwlrForeignToplevelHandleV1,
+        event: zwlr_foreign_toplevel_handle_v1::Event,
+        data: &ToplevelData,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_foreign_toplevel_handle_v1::Event::AppId { app_id } => {
+                data.0.lock().unwrap().app_id = Some(app_id);
+            }
+            zwlr_foreign_toplevel_handle_v1::Event::State { state: states } => {
+                let states = states
+                    .chunks_exact(4)
+                    .map(|s| u32::from_ne_bytes([s[0], s[1], s[2], s[3]]))
+                    .filter_map(|s| State::try_from(s).ok())
+                    .collect::<Vec<_>>();
+
+                let mut toplevel = data.0.lock().unwrap();
+                toplevel.activated = states.contains(&State::Activated);
+                toplevel.fullscreen = states.contains(&State::Fullscreen);
+            }
+            zwlr_foreign_toplevel_handle_v1::Event::Done => {
+                state.toplevel_changed(&data.state());
+            }
+            zwlr_foreign_toplevel_handle_v1::Event::Closed => {
+                state.toplevel_closed(handle);
+                handle.destroy();
+            }
+            _ => {}
+        }
+    }
+}
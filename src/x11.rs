@@ -0,0 +1,844 @@
+use std::{os::fd::AsRawFd, rc::Rc, time::Duration};
+
+use anyhow::{bail, Context, Result};
+use log::{debug, info, warn};
+use smithay_client_toolkit::reexports::calloop::{
+    generic::{FdWrapper, Generic},
+    timer::{TimeoutAction, Timer},
+    EventLoop, Interest, Mode, PostAction,
+};
+use x11rb::{
+    connection::{Connection, RequestConnection},
+    protocol::{
+        randr::{self, ConnectionExt as _},
+        shape::{self, ConnectionExt as _},
+        xinput::{self, ConnectionExt as _},
+        xproto::{
+            ChangeWindowAttributesAux, ClipOrdering, ColormapAlloc, ConnectionExt as _,
+            CreateWindowAux, GrabMode, GrabStatus, VisualClass, Visualid, Window, WindowClass,
+        },
+        Event as XEvent,
+    },
+    rust_connection::RustConnection,
+    CURRENT_TIME,
+};
+
+use crate::{
+    backend::{Capabilities, DimBackend, Level},
+    backlight::{self, Backlight},
+    buffer::Color,
+    consts::{EVENT_TICK_INTERVAL, INHIBITOR_POLL_INTERVAL, STEP_INTERVAL},
+    control::{self, Controlled, Request},
+    events::Event,
+    hooks::{Hooks, Report},
+    inhibit, signals,
+    state::{DimState, Status},
+    Backend, DimOpts, DimOutcome, EventFormat, InhibitPolicy, InputClass,
+};
+
+/// Dim every monitor of the X11 display until the dim ends, as `dim` does on Wayland, taking
+/// requests through the control socket if told to listen. Only the dim itself is supported, not
+/// the commands waiting on the session.
+pub fn dim(opts: DimOpts, hooks: &Hooks, listen: bool) -> Result<DimOutcome> {
+    dim_display(None, opts, hooks, listen)
+}
+
+/// Like [`dim`], on the given display rather than that of `DISPLAY`
+fn dim_display(
+    display: Option<&str>,
+    opts: DimOpts,
+    hooks: &Hooks,
+    listen: bool,
+) -> Result<DimOutcome> {
+    let mut event_loop: EventLoop<X11Data> =
+        EventLoop::try_new().context("Failed to create event loop")?;
+    // before hooks or the control socket spawn threads, which would otherwise receive signals
+    signals::listen(&event_loop.handle(), opts.extend_by())
+        .context("Failed to listen for signals")?;
+
+    let ticks = opts.events.is_some();
+    let mut data = X11Data::connect(display, opts)?;
+
+    // SAFETY: the connection is only dropped along with the data, once we stop dispatching
+    let fd = unsafe { FdWrapper::new(data.conn.stream().as_raw_fd()) };
+    event_loop
+        .handle()
+        .insert_source(Generic::new(fd, Interest::READ, Mode::Level), |_, _, _| {
+            // events are read after every dispatch, as replies may have queued some already
+            Ok(PostAction::Continue)
+        })
+        .map_err(|e| e.error)
+        .context("Failed to insert X11 source")?;
+
+    if listen {
+        if let Err(e) = control::listen(&event_loop.handle()) {
+            warn!("Not listening for requests: {e}");
+        }
+    }
+
+    if data.inhibit_records {
+        event_loop
+            .handle()
+            .insert_source(
+                Timer::from_duration(INHIBITOR_POLL_INTERVAL),
+                |_, _, data: &mut X11Data| {
+                    data.poll_inhibitors();
+                    TimeoutAction::ToDuration(INHIBITOR_POLL_INTERVAL)
+                },
+            )
+            .map_err(|e| e.error)
+            .context("Failed to insert inhibitor timer")?;
+    }
+
+    if ticks {
+        event_loop
+            .handle()
+            .insert_source(
+                Timer::from_duration(EVENT_TICK_INTERVAL),
+                |_, _, data: &mut X11Data| {
+                    data.tick();
+                    TimeoutAction::ToDuration(EVENT_TICK_INTERVAL)
+                },
+            )
+            .map_err(|e| e.error)
+            .context("Failed to insert event tick timer")?;
+    }
+
+    data.start();
+
+    loop {
+        data.handle_events()?;
+
+        if std::mem::take(&mut data.started) {
+            hooks.started(&data.report());
+        }
+
+        if let Some(outcome) = data.poll_outcome() {
+            hooks.ended(&data.report());
+            return Ok(outcome);
+        }
+
+        event_loop
+            .dispatch(data.dispatch_timeout(), &mut data)
+            .context("Failed to dispatch events!")?;
+    }
+}
+
+/// A dim of an X11 display, run like [`DimData`](crate::DimData) runs those of Wayland
+struct X11Data {
+    conn: Rc<RustConnection>,
+    /// Kept apart from the other backends, as RandR tells us about its monitors
+    overlay: Option<X11Overlay>,
+    /// Backends dimming along with the overlay, those failing to prepare being left out
+    backends: Vec<Box<dyn DimBackend>>,
+
+    state: DimState,
+    dimming: bool,
+    /// Whether a dim started since the hooks were last told
+    started: bool,
+    report: Report,
+
+    /// Whether to check for `dim inhibit` before and while dimming
+    inhibit_records: bool,
+    /// Whether an inhibitor ends the dim, rather than being waited out
+    exit_on_inhibitor: bool,
+    /// Whether to start the dim on the next poll, as we wait for inhibitors to clear
+    inhibitor_wait: bool,
+}
+
+impl X11Data {
+    fn connect(display: Option<&str>, opts: DimOpts) -> Result<Self> {
+        let (conn, screen_num) =
+            x11rb::connect(display).context("Failed to connect to X11 display")?;
+        let conn = Rc::new(conn);
+        let root = conn.setup().roots[screen_num].root;
+
+        // raw events of master devices need 2.1, touches 2.2
+        let version = conn
+            .xinput_xi_query_version(2, 2)?
+            .reply()
+            .context("XInput 2 not available")?;
+        if (version.major_version, version.minor_version) < (2, 1) {
+            bail!(
+                "XInput {}.{} is too old, raw events need 2.1",
+                version.major_version,
+                version.minor_version
+            );
+        }
+        conn.xinput_xi_select_events(
+            root,
+            &[xinput::EventMask {
+                deviceid: xinput::Device::ALL_MASTER.into(),
+                mask: vec![
+                    xinput::XIEventMask::RAW_KEY_PRESS
+                        | xinput::XIEventMask::RAW_BUTTON_PRESS
+                        | xinput::XIEventMask::RAW_MOTION
+                        | xinput::XIEventMask::RAW_TOUCH_BEGIN,
+                ],
+            }],
+        )?
+        .check()
+        .context("Failed to listen for input")?;
+
+        warn_unsupported(&opts);
+        let backends = opts.backends();
+        let overlay = match backends.contains(&Backend::Overlay) {
+            true => Some(X11Overlay::new(
+                conn.clone(),
+                screen_num,
+                opts.passthrough,
+                opts.events,
+            )?),
+            false => None,
+        };
+
+        let mut others: Vec<Box<dyn DimBackend>> = Vec::new();
+        if backends.contains(&Backend::Backlight) {
+            // brightness left behind by a crashed instance should not wait for our dim
+            backlight::recover_leftovers();
+            others.push(Box::new(Backlight::new(opts.backlight_root())));
+        }
+
+        let data = Self {
+            conn,
+            overlay,
+            backends: others,
+
+            state: DimState::new(&opts),
+            dimming: false,
+            started: false,
+            report: Report::default(),
+
+            inhibit_records: opts.on_inhibit() != InhibitPolicy::Ignore,
+            exit_on_inhibitor: opts.on_inhibit() == InhibitPolicy::Exit,
+            inhibitor_wait: false,
+        };
+
+        data.state.emit(Event::Connected { backend: "x11" });
+        Ok(data)
+    }
+
+    /// Backends dimming the current dim, the overlay first
+    fn backends_mut(&mut self) -> impl Iterator<Item = &mut (dyn DimBackend + 'static)> {
+        let overlay = self
+            .overlay
+            .iter_mut()
+            .map(|overlay| overlay as &mut (dyn DimBackend + 'static));
+        let others = self.backends.iter_mut().map(|backend| backend.as_mut());
+        overlay.chain(others)
+    }
+
+    /// Tell how much time is left, if we are dimming and printing events.
+    fn tick(&self) {
+        if self.running() {
+            self.state.emit(Event::Tick {
+                remaining: self.time_left().map(|left| left.as_secs_f32()),
+            });
+        }
+    }
+
+    /// Dim every monitor with every backend, starting the fade and timer from now, unless
+    /// `dim inhibit` keeps us from it.
+    fn start(&mut self) {
+        if self.inhibit_records && inhibit::held() {
+            debug!("Inhibited by `dim inhibit`");
+            if self.exit_on_inhibitor {
+                self.state.outcome = Some(DimOutcome::Inhibited);
+            } else {
+                info!("Dimming is inhibited, waiting for inhibitors to clear...");
+                self.inhibitor_wait = true;
+            }
+            return;
+        }
+
+        debug!("Starting dim");
+        self.dimming = true;
+        if let Some(overlay) = &mut self.overlay {
+            if let Err(e) = overlay.prepare() {
+                warn!("Not dimming with {}: {e:#}", overlay.name());
+                self.overlay = None;
+            }
+        }
+        self.backends.retain_mut(|backend| {
+            backend
+                .prepare()
+                .inspect_err(|e| warn!("Not dimming with {}: {e:#}", backend.name()))
+                .is_ok()
+        });
+
+        self.started = true;
+        self.state.start();
+    }
+
+    /// Restore what we dimmed.
+    fn stop(&mut self) {
+        debug!("Stopping dim");
+        self.dimming = false;
+        self.state.stop();
+        for backend in self.backends_mut() {
+            backend.restore();
+        }
+    }
+
+    /// Whether the dim is showing and may still end in any way
+    fn running(&self) -> bool {
+        self.dimming && self.state.running()
+    }
+
+    /// Time left until the dim completes, if it is running and not infinite.
+    fn time_left(&self) -> Option<Duration> {
+        self.state.time_left().filter(|_| self.dimming)
+    }
+
+    /// What we know about the current dim, or the last one once it has ended
+    fn report(&self) -> Report {
+        match self.dimming {
+            true => Report {
+                outcome: self.state.outcome,
+                elapsed: self.state.timeline.elapsed(),
+                cancel_reason: self.state.cancel_reason,
+                outputs: self
+                    .overlay
+                    .as_ref()
+                    .map(X11Overlay::monitor_names)
+                    .unwrap_or_default(),
+            },
+            false => self.report.clone(),
+        }
+    }
+
+    /// How long to wait for events at most before calling [`X11Data::poll_outcome`] again.
+    fn dispatch_timeout(&self) -> Option<Duration> {
+        [
+            self.time_left(),
+            self.state.fade_out_left(),
+            Some(STEP_INTERVAL).filter(|_| self.dimming && self.state.timeline.animating()),
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+    }
+
+    /// Take the outcome of the dim once it has ended, stopping it.
+    fn poll_outcome(&mut self) -> Option<DimOutcome> {
+        if self.dimming {
+            let level = self.state.step();
+            for backend in self.backends_mut() {
+                backend.apply(level);
+            }
+        }
+
+        if self.state.outcome.is_none() && self.time_left() == Some(Duration::ZERO) {
+            self.state.outcome = Some(DimOutcome::Completed);
+        }
+        self.state.poll_fade_out();
+
+        self.state.outcome?;
+        // the dim may have ended before it started, e.g. when inhibited
+        let report = match self.dimming {
+            true => self.report(),
+            false => Report::default(),
+        };
+        let (outcome, report) = self.state.take_outcome(report)?;
+        self.report = report;
+
+        self.stop();
+        if let Err(e) = self.conn.flush() {
+            warn!("Failed to flush requests: {e}");
+        }
+        self.state.emit(Event::Outcome {
+            outcome,
+            code: outcome.exit_code(),
+        });
+        Some(outcome)
+    }
+
+    /// Start the dim if we were waiting for inhibitors to clear, or end it if one appeared.
+    fn poll_inhibitors(&mut self) {
+        if self.inhibitor_wait {
+            self.inhibitor_wait = false;
+            self.start();
+        } else if self.running() && inhibit::held() {
+            debug!("Inhibitor appeared while dimming");
+            self.state.outcome = Some(DimOutcome::Inhibited);
+        }
+    }
+
+    /// Act on every event the server has sent us so far.
+    fn handle_events(&mut self) -> Result<()> {
+        while let Some(event) = self
+            .conn
+            .poll_for_event()
+            .context("Lost connection to X11 display")?
+        {
+            let class = match event {
+                XEvent::XinputRawKeyPress(_) => InputClass::Keyboard,
+                XEvent::XinputRawButtonPress(_) | XEvent::XinputRawMotion(_) => InputClass::Pointer,
+                XEvent::XinputRawTouchBegin(_) => InputClass::Touch,
+                XEvent::RandrScreenChangeNotify(_) => {
+                    if let Some(overlay) = self.overlay.as_mut().filter(|_| self.dimming) {
+                        overlay.update_monitors()?;
+                    }
+                    continue;
+                }
+                XEvent::Error(e) => {
+                    debug!("X11 error: {e:?}");
+                    continue;
+                }
+                _ => continue,
+            };
+
+            self.cancel(class);
+        }
+
+        Ok(())
+    }
+
+    /// Called whenever any user input is detected, making us exit.
+    fn cancel(&mut self, class: InputClass) {
+        if !self.running() {
+            return;
+        }
+
+        debug!("{class:?} input detected");
+        self.state.cancel(class, None);
+    }
+}
+
+impl Controlled for X11Data {
+    fn handle_request(&mut self, request: Request) -> Result<String> {
+        let status = match (self.running(), self.inhibitor_wait) {
+            (true, _) => Status::Dimming,
+            (false, true) => Status::Inhibited,
+            (false, false) => Status::Idle,
+        };
+        self.state.handle_request(request, status)
+    }
+
+    fn interrupt(&mut self) {
+        self.state.interrupt(self.running());
+    }
+}
+
+/// Override-redirect windows over every monitor, only translucent under a compositing manager
+struct X11Overlay {
+    conn: Rc<RustConnection>,
+    root: Window,
+    /// Visual with an alpha channel, which a compositing manager blends our windows with
+    visual: Visualid,
+    colormap: u32,
+    passthrough: bool,
+    has_shape: bool,
+    monitors: Vec<Monitor>,
+    /// Alpha and color last shown by our windows
+    shown: Option<(f32, Color)>,
+    /// Format to tell monitors coming and going in, if asked to
+    events: Option<EventFormat>,
+}
+
+/// A monitor as told by RandR, along with the window dimming it
+#[derive(Debug, Clone, PartialEq)]
+struct Monitor {
+    name: String,
+    x: i16,
+    y: i16,
+    width: u16,
+    height: u16,
+    window: Option<Window>,
+}
+
+impl X11Overlay {
+    fn new(
+        conn: Rc<RustConnection>,
+        screen_num: usize,
+        passthrough: bool,
+        events: Option<EventFormat>,
+    ) -> Result<Self> {
+        let screen = &conn.setup().roots[screen_num];
+        let root = screen.root;
+
+        let visual = screen
+            .allowed_depths
+            .iter()
+            .filter(|depth| depth.depth == 32)
+            .flat_map(|depth| &depth.visuals)
+            .find(|visual| visual.class == VisualClass::TRUE_COLOR)
+            .map(|visual| visual.visual_id)
+            .context("No 32 bit visual to show translucent windows with")?;
+
+        // monitors may be changed while we dim
+        if let Err(e) = conn
+            .randr_select_input(root, randr::NotifyMask::SCREEN_CHANGE)
+            .map(|cookie| cookie.ignore_error())
+        {
+            warn!("Not following monitor changes: {e}");
+        }
+
+        let has_shape = conn
+            .extension_information(shape::X11_EXTENSION_NAME)?
+            .is_some();
+        if passthrough && !has_shape {
+            warn!("Shape extension not available, our windows will take input.");
+        }
+
+        let colormap = conn.generate_id()?;
+        conn.create_colormap(ColormapAlloc::NONE, colormap, root, visual)?;
+
+        Ok(Self {
+            conn,
+            root,
+            visual,
+            colormap,
+            passthrough,
+            has_shape,
+            monitors: Vec::new(),
+            shown: None,
+            events,
+        })
+    }
+
+    fn emit(&self, event: Event) {
+        if let Some(format) = self.events {
+            event.emit(format);
+        }
+    }
+
+    fn monitor_names(&self) -> Vec<String> {
+        self.monitors.iter().map(|m| m.name.clone()).collect()
+    }
+
+    /// Take the keyboard from other clients, as our windows take the pointer.
+    fn grab_keyboard(&self) {
+        let status = self
+            .conn
+            .grab_keyboard(
+                false,
+                self.root,
+                CURRENT_TIME,
+                GrabMode::ASYNC,
+                GrabMode::ASYNC,
+            )
+            .map_err(anyhow::Error::from)
+            .and_then(|cookie| Ok(cookie.reply()?.status));
+
+        match status {
+            Ok(GrabStatus::SUCCESS) => {}
+            Ok(status) => debug!("Failed to grab the keyboard: {status:?}"),
+            Err(e) => debug!("Failed to grab the keyboard: {e}"),
+        }
+    }
+
+    /// Show a window over every monitor, replacing those of monitors which have changed.
+    fn update_monitors(&mut self) -> Result<()> {
+        let mut monitors = self.query_monitors()?;
+        for monitor in std::mem::take(&mut self.monitors) {
+            let unchanged = Monitor {
+                window: None,
+                ..monitor.clone()
+            };
+            match monitors.iter_mut().find(|m| **m == unchanged) {
+                Some(m) => m.window = monitor.window,
+                None => self.remove_monitor(monitor),
+            }
+        }
+
+        let pixel = self.shown.map(|(alpha, color)| argb(alpha, color));
+        for monitor in &mut monitors {
+            if monitor.window.is_none() {
+                monitor.window = Some(self.create_window(monitor, pixel.unwrap_or(0))?);
+                self.emit(Event::OutputAdded {
+                    name: Some(monitor.name.clone()),
+                    width: Some(monitor.width.into()),
+                    height: Some(monitor.height.into()),
+                });
+            }
+        }
+
+        self.monitors = monitors;
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    /// Monitors as told by RandR, or the whole screen without it
+    fn query_monitors(&self) -> Result<Vec<Monitor>> {
+        let reply = self
+            .conn
+            .randr_get_monitors(self.root, true)
+            .map_err(anyhow::Error::from)
+            .and_then(|cookie| Ok(cookie.reply()?));
+
+        let monitors = match reply {
+            Ok(reply) if !reply.monitors.is_empty() => reply.monitors,
+            result => {
+                if let Err(e) = result {
+                    warn!("RandR monitors not available, dimming the whole screen: {e}");
+                }
+                let geometry = self.conn.get_geometry(self.root)?.reply()?;
+                return Ok(vec![Monitor {
+                    name: "screen".to_owned(),
+                    x: 0,
+                    y: 0,
+                    width: geometry.width,
+                    height: geometry.height,
+                    window: None,
+                }]);
+            }
+        };
+
+        monitors
+            .into_iter()
+            .map(|info| {
+                let name = self.conn.get_atom_name(info.name)?.reply()?.name;
+                Ok(Monitor {
+                    name: String::from_utf8_lossy(&name).into_owned(),
+                    x: info.x,
+                    y: info.y,
+                    width: info.width,
+                    height: info.height,
+                    window: None,
+                })
+            })
+            .collect()
+    }
+
+    /// Create a window showing the given pixel over the given monitor.
+    fn create_window(&self, monitor: &Monitor, pixel: u32) -> Result<Window> {
+        let window = self.conn.generate_id()?;
+        let aux = CreateWindowAux::new()
+            .background_pixel(pixel)
+            // a visual differing from the root's needs a border pixel and colormap of its own
+            .border_pixel(0)
+            .colormap(self.colormap)
+            .override_redirect(1);
+        self.conn.create_window(
+            32,
+            window,
+            self.root,
+            monitor.x,
+            monitor.y,
+            monitor.width,
+            monitor.height,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            self.visual,
+            &aux,
+        )?;
+
+        if self.passthrough && self.has_shape {
+            // an empty input shape lets input through to the windows below
+            self.conn.shape_rectangles(
+                shape::SO::SET,
+                shape::SK::INPUT,
+                ClipOrdering::UNSORTED,
+                window,
+                0,
+                0,
+                &[],
+            )?;
+        }
+
+        self.conn.map_window(window)?;
+        Ok(window)
+    }
+
+    fn remove_monitor(&self, monitor: Monitor) {
+        if let Some(window) = monitor.window {
+            let _ = self.conn.destroy_window(window);
+            self.emit(Event::OutputRemoved {
+                name: Some(monitor.name),
+            });
+        }
+    }
+}
+
+impl DimBackend for X11Overlay {
+    fn name(&self) -> &str {
+        "x11"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            color: true,
+            // RandR tells us about monitors, not the compositor
+            per_output: false,
+            input: !self.passthrough,
+        }
+    }
+
+    fn prepare(&mut self) -> Result<()> {
+        self.update_monitors()?;
+        if !self.passthrough {
+            self.grab_keyboard();
+        }
+        Ok(())
+    }
+
+    /// Paint our windows with the alpha and color of the level, if not already.
+    fn apply(&mut self, level: Level) {
+        if self.shown == Some((level.alpha, level.color)) {
+            return;
+        }
+
+        let pixel = argb(level.alpha, level.color);
+        for window in self.monitors.iter().filter_map(|m| m.window) {
+            let aux = ChangeWindowAttributesAux::new().background_pixel(pixel);
+            let result = self
+                .conn
+                .change_window_attributes(window, &aux)
+                .and_then(|_| self.conn.clear_area(false, window, 0, 0, 0, 0));
+            if let Err(e) = result {
+                warn!("Failed to paint window {window}: {e}");
+            }
+        }
+
+        if let Err(e) = self.conn.flush() {
+            warn!("Failed to flush requests: {e}");
+        }
+        self.shown = Some((level.alpha, level.color));
+    }
+
+    fn restore(&mut self) {
+        for monitor in std::mem::take(&mut self.monitors) {
+            self.remove_monitor(monitor);
+        }
+        let _ = self.conn.ungrab_keyboard(CURRENT_TIME);
+        self.shown = None;
+    }
+}
+
+/// Premultiplied ARGB pixel showing the given color at the given alpha
+fn argb(alpha: f32, color: Color) -> u32 {
+    let alpha = alpha.clamp(0., 1.);
+    let channel = |value: u8| (f32::from(value) * alpha).round() as u32;
+    ((alpha * 255.).round() as u32) << 24
+        | channel(color.r) << 16
+        | channel(color.g) << 8
+        | channel(color.b)
+}
+
+/// Warn about every option which only Wayland compositors let us honor.
+fn warn_unsupported(opts: &DimOpts) {
+    let unsupported = [
+        ("then", opts.then.is_some()),
+        ("then_opaque", opts.then_opaque),
+        ("power_off_on_timeout", opts.power_off_on_timeout),
+        ("skip_if_fullscreen", opts.skip_if_fullscreen),
+        ("exempt_app_ids", !opts.exempt_app_ids.is_empty()),
+        ("seats", !opts.seats.is_empty()),
+        ("mpris", opts.mpris),
+        ("ignore_players", !opts.ignore_players.is_empty()),
+        (
+            "idle inhibitors other than `dim inhibit`",
+            opts.on_inhibit.is_some() && opts.checks_idle_inhibitors(),
+        ),
+        ("gamma backend", opts.backends().contains(&Backend::Gamma)),
+    ];
+
+    for (name, set) in unsupported {
+        if set {
+            warn!("Ignoring {name}, not supported on X11.");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::Path,
+        process::{Child, Command, Stdio},
+        thread,
+        time::Instant,
+    };
+
+    use x11rb::protocol::xproto::MapState;
+
+    use super::*;
+
+    /// How long Xvfb and our dim get to do what we expect
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// A display of our own, killed once dropped
+    struct Xvfb {
+        server: Child,
+        display: String,
+    }
+
+    impl Xvfb {
+        fn start() -> Self {
+            let number = (99..)
+                .find(|n| !Path::new(&format!("/tmp/.X{n}-lock")).exists())
+                .expect("a display number is free");
+            let display = format!(":{number}");
+            let server = Command::new("Xvfb")
+                .args([
+                    display.as_str(),
+                    "-screen",
+                    "0",
+                    "640x480x24",
+                    "-nolisten",
+                    "tcp",
+                ])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+                .expect("Xvfb is needed to test dimming X11");
+
+            let deadline = Instant::now() + TIMEOUT;
+            while x11rb::connect(Some(&display)).is_err() {
+                assert!(Instant::now() < deadline, "Xvfb did not start");
+                thread::sleep(Duration::from_millis(50));
+            }
+
+            Self { server, display }
+        }
+
+        /// Windows mapped over the root window by other clients
+        fn mapped_windows(&self, conn: &RustConnection, root: Window) -> Vec<Window> {
+            let tree = conn.query_tree(root).unwrap().reply().unwrap();
+            tree.children
+                .into_iter()
+                .filter(|&window| {
+                    let attributes = conn.get_window_attributes(window).unwrap().reply();
+                    attributes.is_ok_and(|a| a.map_state == MapState::VIEWABLE)
+                })
+                .collect()
+        }
+    }
+
+    impl Drop for Xvfb {
+        fn drop(&mut self) {
+            let _ = self.server.kill();
+            let _ = self.server.wait();
+        }
+    }
+
+    #[test]
+    #[ignore = "needs Xvfb"]
+    fn dims_until_duration_passes() {
+        let xvfb = Xvfb::start();
+        let (conn, screen_num) = x11rb::connect(Some(&xvfb.display)).unwrap();
+        let root = conn.setup().roots[screen_num].root;
+
+        let display = xvfb.display.clone();
+        let started = Instant::now();
+        let dim = thread::spawn(move || {
+            let mut opts = DimOpts::default();
+            opts.duration = Some(1);
+            opts.fade = Some(0.);
+            dim_display(Some(&display), opts, &Hooks::default(), false).unwrap()
+        });
+
+        while xvfb.mapped_windows(&conn, root).is_empty() {
+            assert!(started.elapsed() < TIMEOUT, "no window was mapped");
+            thread::sleep(Duration::from_millis(20));
+        }
+        assert!(
+            started.elapsed() < Duration::from_secs(1),
+            "windows were mapped only once the dim should have completed"
+        );
+
+        assert_eq!(dim.join().unwrap(), DimOutcome::Completed);
+        assert!(started.elapsed() >= Duration::from_secs(1));
+        assert!(xvfb.mapped_windows(&conn, root).is_empty());
+    }
+}